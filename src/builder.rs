@@ -0,0 +1,227 @@
+use data::*;
+use layout::LayoutManager;
+use layout::constraint::LayoutConstraint;
+use layout::display::{BlockDisplay, ConnectionDisplay};
+use parser::nodes::remove_common_indent;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use writer;
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum BuildError {
+  /// Block names must be made of ASCII letters and digits
+  InvalidName(String),
+  DuplicateName(String),
+  UnknownBlock(String),
+  /// A connection from a block to itself
+  SelfLoop(String),
+  /// Block text that is empty or contains `"""`. Holds the block name.
+  InvalidText(String),
+  /// A label that is empty or contains quotes or line breaks
+  InvalidLabel(String)
+}
+
+impl Display for BuildError {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    use self::BuildError::*;
+    match *self {
+      InvalidName(ref name) => write!(f, "invalid block name {:?}", name),
+      DuplicateName(ref name) => write!(f, "block {} is already defined", name),
+      UnknownBlock(ref name) => write!(f, "connection refers to unknown block {}", name),
+      SelfLoop(ref name) => write!(f, "connection from block {} to itself", name),
+      InvalidText(ref name) => write!(f, "block {} has text that can't be written out", name),
+      InvalidLabel(ref label) => write!(f, "invalid connection label {:?}", label)
+    }
+  }
+}
+
+impl Error for BuildError {
+  fn description(&self) -> &str {
+    use self::BuildError::*;
+    match *self {
+      InvalidName(_) => "block names must be ASCII letters and digits",
+      DuplicateName(_) => "block name is already defined",
+      UnknownBlock(_) => "connection refers to an unknown block",
+      SelfLoop(_) => "connection from a block to itself",
+      InvalidText(_) => "block text is empty or contains a heredoc delimiter",
+      InvalidLabel(_) => "labels can't be empty or contain quotes or line breaks"
+    }
+  }
+}
+
+/// Builds a diagram in code rather than from `.cog` source.
+///
+/// Every block and connection is checked as it is added, so a finished
+/// builder always holds a diagram that can be laid out and that reads
+/// back the same from its `.cog` source.
+pub struct DiagramBuilder {
+  blocks: Vec<BlockSpec>,
+  connections: Vec<Connection>
+}
+
+impl DiagramBuilder {
+  pub fn new() -> DiagramBuilder {
+    DiagramBuilder{blocks: vec![], connections: vec![]}
+  }
+
+  /// Adds a box. Lines of text are separated by '\n'. Trailing whitespace
+  /// and indentation shared by every line are dropped.
+  pub fn add_box(&mut self, name: &str, text: &str, color: Coloring)
+      -> Result<&mut DiagramBuilder, BuildError> {
+    if name.is_empty() || !name.chars().all(|c| (c as u32) < 128 && c.is_alphanumeric()) {
+      return Err(BuildError::InvalidName(name.to_owned()));
+    }
+    if self.has_block(name) {
+      return Err(BuildError::DuplicateName(name.to_owned()));
+    }
+
+    let text = normalize_text(text);
+    if text.is_empty() || text.contains("\"\"\"") {
+      return Err(BuildError::InvalidText(name.to_owned()));
+    }
+
+    self.blocks.push(BlockSpec::Boxed(name.to_owned(), color, text));
+    Ok(self)
+  }
+
+  pub fn connect(&mut self, ty: ConnectionType, start: &str, end: &str, color: Coloring)
+      -> Result<&mut DiagramBuilder, BuildError> {
+    self.add_connection(Connection{
+      ty: ty,
+      start: start.to_owned(),
+      end: end.to_owned(),
+      color: color,
+      label: None})
+  }
+
+  /// Adds a connection, which may have a label. Both blocks must
+  /// already have been added.
+  pub fn add_connection(&mut self, conn: Connection) -> Result<&mut DiagramBuilder, BuildError> {
+    for name in [&conn.start, &conn.end].iter() {
+      if !self.has_block(name) {
+        return Err(BuildError::UnknownBlock((*name).clone()));
+      }
+    }
+    if conn.start == conn.end {
+      return Err(BuildError::SelfLoop(conn.start.clone()));
+    }
+    for label in conn.label.iter() {
+      if label.is_empty() || label.contains(|c: char| c == '"' || c == '\r' || c == '\n') {
+        return Err(BuildError::InvalidLabel(label.clone()));
+      }
+    }
+
+    self.connections.push(conn);
+    Ok(self)
+  }
+
+  pub fn blocks(&self) -> &[BlockSpec] {
+    &self.blocks
+  }
+
+  pub fn connections(&self) -> &[Connection] {
+    &self.connections
+  }
+
+  /// The diagram as specifications, blocks first, as the parser would
+  /// return them.
+  pub fn specs(&self) -> Vec<DataSpec> {
+    self.blocks.iter().cloned().map(DataSpec::BlockDataSpec)
+      .chain(self.connections.iter().cloned().map(DataSpec::ConnectionDataSpec))
+      .collect()
+  }
+
+  /// The diagram as `.cog` source
+  pub fn to_source(&self) -> String {
+    writer::to_source(&self.specs())
+  }
+
+  /// Lays the diagram out with `layout`, then hands the result to
+  /// `renderer`, returning whatever it does.
+  pub fn render<F, R>(&self, layout: &LayoutManager, constraint: &LayoutConstraint, renderer: F) -> R
+      where F: FnOnce(&[(&BlockSpec, BlockDisplay)], &[ConnectionDisplay]) -> R {
+    let blocks = layout.determine_block_vector_layout(&self.blocks, &self.connections, &constraint.block);
+    let connections = layout.determine_connection_layout(&self.connections, &blocks, constraint);
+    renderer(&blocks, &connections)
+  }
+
+  fn has_block(&self, name: &str) -> bool {
+    self.blocks.iter().any(|b| b.get_name() == name)
+  }
+}
+
+fn normalize_text(text: &str) -> String {
+  let lines:Vec<&str> = text.trim_matches('\n').split('\n').map(|l| l.trim_right()).collect();
+  remove_common_indent(&lines)
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use data::*;
+  use layout::charset::CharSet;
+  use layout::registry::{create_layout_manager, LayoutOptions};
+  use config::Settings;
+  use parser::driver::parse_specification;
+  use render::text::render_to_string;
+
+  fn diagram() -> DiagramBuilder {
+    let mut diagram = DiagramBuilder::new();
+    diagram.add_box("web", "Web server", Coloring::Green).unwrap()
+      .add_box("db", "  Database\n\n  primary  ", Coloring::Default).unwrap()
+      .add_connection(Connection{
+        ty: ConnectionType::Singular,
+        start: "web".to_owned(),
+        end: "db".to_owned(),
+        color: Coloring::Red,
+        label: Some("sql".to_owned())}).unwrap();
+    diagram
+  }
+
+  #[test]
+  fn checks_as_it_goes() {
+    let mut diagram = diagram();
+    assert_eq!(diagram.add_box("web", "again", Coloring::Default).err(),
+               Some(BuildError::DuplicateName("web".to_owned())));
+    assert_eq!(diagram.add_box("web-2", "dash", Coloring::Default).err(),
+               Some(BuildError::InvalidName("web-2".to_owned())));
+    assert_eq!(diagram.connect(ConnectionType::Dual, "web", "cache", Coloring::Default).err(),
+               Some(BuildError::UnknownBlock("cache".to_owned())));
+    assert_eq!(diagram.connect(ConnectionType::Dual, "db", "db", Coloring::Default).err(),
+               Some(BuildError::SelfLoop("db".to_owned())));
+    assert_eq!(diagram.blocks().len(), 2);
+    assert_eq!(diagram.connections().len(), 1);
+  }
+
+  #[test]
+  fn round_trips_to_source() {
+    let diagram = diagram();
+    assert_eq!(diagram.blocks()[1],
+               BlockSpec::Boxed("db".to_owned(), Coloring::Default, "Database\n\nprimary".to_owned()));
+    assert_eq!(parse_specification(diagram.to_source().as_bytes()).unwrap(), diagram.specs());
+  }
+
+  #[test]
+  fn keeps_indentation_that_is_not_spaces() {
+    let mut diagram = diagram();
+    diagram.add_box("wide", "\u{3000}wide\n  narrow", Coloring::Default).unwrap();
+    assert_eq!(diagram.blocks()[2],
+               BlockSpec::Boxed("wide".to_owned(), Coloring::Default, "\u{3000}wide\n  narrow".to_owned()));
+    assert_eq!(parse_specification(diagram.to_source().as_bytes()).unwrap(), diagram.specs());
+  }
+
+  #[test]
+  fn lays_out_and_renders() {
+    let settings = Settings::new();
+    let diagram = diagram();
+    let layout = create_layout_manager("layered", &LayoutOptions{
+      screen_width: settings.screen_width,
+      screen_height: settings.screen_height,
+      max_width: settings.constraint.max_width}).unwrap();
+    let text = diagram.render(&*layout, &settings.constraint,
+                              |blocks, conns| render_to_string(blocks, conns, CharSet::Ascii));
+    assert!(text.contains("Web server"));
+    assert!(text.contains("sql"));
+  }
+}
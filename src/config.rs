@@ -0,0 +1,205 @@
+use layout::charset::CharSet;
+use layout::constraint::{LayoutConstraint, BlockConstraint, ConnectionConstraint};
+use layout::registry::DEFAULT_LAYOUT;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Name of the per-project configuration file. It is looked for in the
+/// directory of the input file and each of its parents.
+pub const CONFIG_FILE_NAME: &'static str = "cog.conf";
+
+#[derive(Debug)]
+pub enum ConfigError {
+  /// A line that isn't blank, a comment or a `key = value` pair
+  Syntax(usize),
+  UnknownKey(String),
+  InvalidValue(String, String),
+  IoErr(io::Error)
+}
+
+impl From<io::Error> for ConfigError {
+  fn from(e: io::Error) -> ConfigError {
+    ConfigError::IoErr(e)
+  }
+}
+
+impl Display for ConfigError {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    use self::ConfigError::*;
+    match *self {
+      Syntax(line) => write!(f, "expected key = value on line {}", line),
+      UnknownKey(ref key) => write!(f, "unknown setting {}", key),
+      InvalidValue(ref key, ref value) => write!(f, "invalid value {} for setting {}", value, key),
+      IoErr(ref e) => write!(f, "could not read configuration: {}", e)
+    }
+  }
+}
+
+impl Error for ConfigError {
+  fn description(&self) -> &str {
+    use self::ConfigError::*;
+    match *self {
+      Syntax(_) => "configuration line is not a key = value pair",
+      UnknownKey(_) => "unknown configuration setting",
+      InvalidValue(_, _) => "invalid value for configuration setting",
+      IoErr(_) => "could not read configuration due to an I/O error"
+    }
+  }
+
+  fn cause(&self) -> Option<&Error> {
+    match *self {
+      ConfigError::IoErr(ref e) => Some(e),
+      _ => None
+    }
+  }
+}
+
+/// Everything that controls how a diagram is laid out.
+///
+/// Settings start at their defaults, then get overridden by the project
+/// configuration file and finally by command line options. Setting names
+/// are the same in the file and on the command line.
+pub struct Settings {
+  pub constraint: LayoutConstraint,
+  pub screen_width: u32,
+  pub screen_height: u32,
+  /// Name of the layout manager to use
+  pub layout: String,
+  /// Characters blocks and connections are drawn with
+  pub charset: CharSet
+}
+
+impl Settings {
+  pub fn new() -> Settings {
+    Settings {
+      constraint: LayoutConstraint {
+        connection: ConnectionConstraint {
+          min_length: 10,
+          max_length: 1000,
+          box_distance: 3
+        },
+        block: BlockConstraint {
+          min_limited_width: 40,
+          max_height_per_width: 1,
+          max_width_per_height: 10,
+          inter_block_distance: 5
+        },
+        max_width: 150,
+        max_height: 100
+      },
+      screen_width: 50,
+      screen_height: 50,
+      layout: DEFAULT_LAYOUT.to_owned(),
+      charset: CharSet::Ascii
+    }
+  }
+
+  pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+    if key == "layout" {
+      self.layout = value.trim().to_owned();
+      return Ok(());
+    }
+    if key == "charset" {
+      self.charset =
+        try!(CharSet::from_name(value.trim()).ok_or(
+          ConfigError::InvalidValue(key.to_owned(), value.to_owned())));
+      return Ok(());
+    }
+
+    let number =
+      try!(value.trim().parse::<u32>().map_err(
+        |_| ConfigError::InvalidValue(key.to_owned(), value.to_owned())));
+
+    match key {
+      "min-block-width" => self.constraint.block.min_limited_width = number,
+      "max-height-per-width" => self.constraint.block.max_height_per_width = number,
+      "max-width-per-height" => self.constraint.block.max_width_per_height = number,
+      "block-spacing" => self.constraint.block.inter_block_distance = number,
+      "min-connection-length" => self.constraint.connection.min_length = number,
+      "max-connection-length" => self.constraint.connection.max_length = number,
+      "box-distance" => self.constraint.connection.box_distance = number,
+      "max-width" => self.constraint.max_width = number,
+      "max-height" => self.constraint.max_height = number,
+      "screen-width" => self.screen_width = number,
+      "screen-height" => self.screen_height = number,
+      _ => return Err(ConfigError::UnknownKey(key.to_owned()))
+    }
+    Ok(())
+  }
+
+  /// Applies every `key = value` line of a configuration file.
+  /// Blank lines and lines starting with `#` are ignored.
+  pub fn apply_config(&mut self, content: &str) -> Result<(), ConfigError> {
+    for (i, line) in content.lines().enumerate() {
+      let line = line.trim();
+      if line.is_empty() || line.starts_with("#") {
+        continue;
+      }
+
+      let mut pair = line.splitn(2, '=');
+      match (pair.next(), pair.next()) {
+        (Some(key), Some(value)) => try!(self.set(key.trim(), value.trim())),
+        _ => return Err(ConfigError::Syntax(i + 1))
+      }
+    }
+    Ok(())
+  }
+
+  pub fn apply_config_file(&mut self, path: &Path) -> Result<(), ConfigError> {
+    let mut content = String::new();
+    try!(try!(File::open(path)).read_to_string(&mut content));
+    self.apply_config(&content)
+  }
+}
+
+/// Finds the configuration file for the project an input file is in.
+pub fn find_project_config(infile: &Path) -> Option<PathBuf> {
+  let mut dir = infile.parent();
+  while let Some(d) = dir {
+    let candidate = d.join(CONFIG_FILE_NAME);
+    if candidate.is_file() {
+      return Some(candidate);
+    }
+    dir = d.parent();
+  }
+  None
+}
+
+#[cfg(test)]
+mod test {
+  use super::{Settings, ConfigError};
+  use layout::charset::CharSet;
+
+  #[test]
+  fn config_overrides_defaults() {
+    let mut settings = Settings::new();
+    settings.apply_config("# spacing for docs\nblock-spacing = 8\n\nmax-width=90\nlayout = layered\ncharset = rounded").unwrap();
+    assert_eq!(settings.constraint.block.inter_block_distance, 8);
+    assert_eq!(settings.layout, "layered");
+    assert_eq!(settings.constraint.max_width, 90);
+    assert_eq!(settings.constraint.max_height, 100);
+    assert_eq!(settings.charset, CharSet::Rounded);
+  }
+
+  #[test]
+  fn config_errors() {
+    let mut settings = Settings::new();
+    match settings.apply_config("max-width = wide") {
+      Err(ConfigError::InvalidValue(ref key, _)) => assert_eq!(key, "max-width"),
+      other => panic!("Expected an invalid value, got {:?}", other)
+    }
+    match settings.apply_config("\nspacing 5") {
+      Err(ConfigError::Syntax(2)) => (),
+      other => panic!("Expected a syntax error, got {:?}", other)
+    }
+    match settings.apply_config("colour = 5") {
+      Err(ConfigError::UnknownKey(ref key)) => assert_eq!(key, "colour"),
+      other => panic!("Expected an unknown key, got {:?}", other)
+    }
+  }
+}
@@ -1,5 +1,3 @@
-use ncurses::*;
-
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum BlockSpec{
   Boxed(String, Coloring, String)
@@ -18,7 +16,8 @@ pub struct Connection {
   pub ty: ConnectionType,
   pub start: String,
   pub end: String,
-  pub color: Coloring
+  pub color: Coloring,
+  pub label: Option<String>
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
@@ -47,49 +46,31 @@ pub enum DataSpec {
   ConnectionDataSpec(Connection)
 }
 
-impl Coloring {
+/// Shorthand for building specifications in tests
+#[cfg(test)]
+pub mod fixtures {
+  use super::*;
 
-  pub fn ncurses_color(&self) -> Option<i16> {
-    use self::Coloring::*;
-    match *self {
-      Default => None,
-      Black => Some(COLOR_BLACK),
-      White => Some(COLOR_WHITE),
-      Red => Some(COLOR_RED),
-      Green => Some(COLOR_GREEN),
-      Yellow => Some(COLOR_YELLOW),
-      Blue => Some(COLOR_BLUE),
-      Magenta => Some(COLOR_MAGENTA),
-      Cyan => Some(COLOR_CYAN)
-    }
+  pub fn boxed(name: &str, color: Coloring, text: &str) -> BlockSpec {
+    BlockSpec::Boxed(name.to_owned(), color, text.to_owned())
   }
 
-  pub fn color_on(&self) {
-    for c in self.ncurses_color() {
-      attron(COLOR_PAIR(c));
-    }
+  pub fn connection(ty: ConnectionType, start: &str, end: &str, color: Coloring, label: Option<&str>)
+      -> Connection {
+    Connection{
+      ty: ty,
+      start: start.to_owned(),
+      end: end.to_owned(),
+      color: color,
+      label: label.map(|l| l.to_owned())}
   }
 
-  pub fn color_off(&self) {
-    for c in self.ncurses_color() {
-      attroff(COLOR_PAIR(c));
-    }
+  pub fn block(name: &str, color: Coloring, text: &str) -> DataSpec {
+    DataSpec::BlockDataSpec(boxed(name, color, text))
   }
 
-  pub fn init_default_color_pairs() {
-    use self::Coloring::*;
-    init_color_pair(White, Black);
-    init_color_pair(Red, Black);
-    init_color_pair(Green, Black);
-    init_color_pair(Yellow, Black);
-    init_color_pair(Blue, Black);
-    init_color_pair(Magenta, Black);
-    init_color_pair(Cyan, Black);
+  pub fn conn(ty: ConnectionType, start: &str, end: &str, color: Coloring, label: Option<&str>)
+      -> DataSpec {
+    DataSpec::ConnectionDataSpec(connection(ty, start, end, color, label))
   }
 }
-
-fn init_color_pair(color_one:Coloring, color_two:Coloring) {
-  let c_one = color_one.ncurses_color().unwrap();
-  let c_two = color_two.ncurses_color().unwrap();
-  init_pair(c_one, c_one, c_two);
-}
@@ -0,0 +1,126 @@
+use data::*;
+use writer::color_name;
+
+/// Writes specifications as a DOT `digraph`.
+///
+/// Blocks become box-shaped nodes labelled with their text. Singular
+/// connections are plain edges, dual connections have `dir=both` and
+/// generic ones `dir=none`. Default colours are left for Graphviz to pick.
+pub fn to_dot(specs: &[DataSpec]) -> String {
+  let mut out = String::from("digraph {\n  node [shape=box];\n");
+
+  for spec in specs.iter() {
+    if let DataSpec::BlockDataSpec(BlockSpec::Boxed(ref name, color, ref text)) = *spec {
+      let mut attrs = vec![format!("label={}", quote(text))];
+      write_color(&mut attrs, color);
+      out.push_str(&format!("  {} [{}];\n", node_id(name), attrs.join(", ")));
+    }
+  }
+
+  for spec in specs.iter() {
+    if let DataSpec::ConnectionDataSpec(ref conn) = *spec {
+      let mut attrs = vec![];
+      match conn.ty {
+        ConnectionType::Singular => (),
+        ConnectionType::Dual => attrs.push("dir=both".to_owned()),
+        ConnectionType::Generic => attrs.push("dir=none".to_owned())
+      }
+      write_color(&mut attrs, conn.color);
+      for label in conn.label.iter() {
+        attrs.push(format!("label={}", quote(label)));
+      }
+
+      out.push_str(&format!("  {} -> {}", node_id(&conn.start), node_id(&conn.end)));
+      if !attrs.is_empty() {
+        out.push_str(&format!(" [{}]", attrs.join(", ")));
+      }
+      out.push_str(";\n");
+    }
+  }
+
+  out.push_str("}\n");
+  out
+}
+
+fn write_color(attrs: &mut Vec<String>, color: Coloring) {
+  if color != Coloring::Default {
+    attrs.push(format!("color={}", color_name(color)));
+  }
+}
+
+/// A block name as a DOT node name. Names that are DOT keywords or start
+/// with a digit are quoted, as they would be read as something else.
+fn node_id(name: &str) -> String {
+  let keyword =
+    ["node", "edge", "graph", "digraph", "subgraph", "strict"].contains(&&name.to_lowercase()[..]);
+  if keyword || name.starts_with(|c: char| c.is_digit(10)) {
+    quote(name)
+  } else {
+    name.to_owned()
+  }
+}
+
+/// A DOT string holding `text`, with line breaks written as `\n`
+fn quote(text: &str) -> String {
+  let mut quoted = String::from("\"");
+  for c in text.chars() {
+    match c {
+      '"' => quoted.push_str("\\\""),
+      '\\' => quoted.push_str("\\\\"),
+      '\n' => quoted.push_str("\\n"),
+      c => quoted.push(c)
+    }
+  }
+  quoted.push('"');
+  quoted
+}
+
+#[cfg(test)]
+mod test {
+  use super::to_dot;
+  use data::*;
+  use data::fixtures::*;
+  use dot::import::parse_dot;
+
+  fn specs() -> Vec<DataSpec> {
+    vec![
+      block("lb", Coloring::Blue, "Load\nbalancer"),
+      block("db", Coloring::Default, "say \"hi\""),
+      conn(ConnectionType::Singular, "lb", "db", Coloring::Default, Some("sql")),
+      conn(ConnectionType::Dual, "db", "lb", Coloring::Red, None),
+      conn(ConnectionType::Generic, "lb", "db", Coloring::Default, None)]
+  }
+
+  #[test]
+  fn quotes_keywords_and_numbers() {
+    let specs = vec![
+      block("Node", Coloring::Default, "a"),
+      block("2b", Coloring::Default, "b"),
+      conn(ConnectionType::Singular, "Node", "2b", Coloring::Default, None)];
+    let dot = to_dot(&specs);
+    assert!(dot.contains("  \"Node\" -> \"2b\";\n"));
+    let read:Vec<DataSpec> =
+      parse_dot(dot.as_bytes()).unwrap().into_iter().map(|(_, spec)| spec).collect();
+    assert_eq!(read, specs);
+  }
+
+  #[test]
+  fn writes_digraph() {
+    assert_eq!(to_dot(&specs()),
+      "digraph {\n  node [shape=box];\n\
+       \x20 lb [label=\"Load\\nbalancer\", color=blue];\n\
+       \x20 db [label=\"say \\\"hi\\\"\"];\n\
+       \x20 lb -> db [label=\"sql\"];\n\
+       \x20 db -> lb [dir=both, color=red];\n\
+       \x20 lb -> db [dir=none];\n\
+       }\n");
+  }
+
+  #[test]
+  fn reads_back_the_same() {
+    let specs = specs();
+    let read:Vec<DataSpec> =
+      parse_dot(to_dot(&specs).as_bytes()).unwrap().into_iter().map(|(_, spec)| spec).collect();
+    assert_eq!(read, specs);
+  }
+}
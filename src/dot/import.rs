@@ -0,0 +1,598 @@
+use data::*;
+use parser::driver::LocatedSpec;
+use parser::location::SourceLocation;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str;
+
+/// A problem reading DOT source, and where it is.
+#[derive(Debug, PartialEq, Eq)]
+pub struct DotError {
+  pub message: String,
+  pub location: SourceLocation
+}
+
+impl Display for DotError {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    write!(f, "{} at {}\n{}", self.message, self.location, self.location.snippet())
+  }
+}
+
+impl Error for DotError {
+  fn description(&self) -> &str {
+    &self.message
+  }
+}
+
+/// Reads a graph written in DOT.
+///
+/// Only the parts of DOT that cog can show are used: each node becomes a
+/// box, using its `label` (or its name) as the text and its `color`, and
+/// each edge becomes a connection. In a `digraph`, `->` edges point at
+/// their second node unless `dir` says otherwise. In a `graph`, `--` edges
+/// have no arrowheads unless `dir` is set. `dir=both` makes a connection
+/// with arrowheads at both ends.
+///
+/// `node` and `edge` defaults and subgraphs are understood, subgraphs
+/// being flattened into the graph. A subgraph or `{...}` group on either
+/// side of an edge stands for every node in it. Ports, HTML labels and
+/// every other attribute are ignored. Node names are reduced to the
+/// letters and digits cog allows in block names.
+pub fn parse_dot(source: &[u8]) -> Result<Vec<LocatedSpec>, DotError> {
+  let text =
+    try!(str::from_utf8(source).map_err(
+      |_| error(source, 0, "DOT source is not valid UTF-8")));
+  let tokens = try!(tokenize(text));
+  let graph = try!(GraphParser{source: source, tokens: &tokens, pos: 0}.parse_graph());
+  Ok(graph.to_specs(source))
+}
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+enum Token {
+  /// A name, number or quoted string, with quotes removed, and whether
+  /// it was quoted. Quoted strings are never keywords.
+  Id(String, bool),
+  /// `->` or `--`
+  EdgeOp,
+  Symbol(char)
+}
+
+/// A token and the byte offset it starts at
+type Lexed = (Token, usize);
+
+fn error(source: &[u8], offset: usize, message: &str) -> DotError {
+  DotError{message: message.to_owned(), location: SourceLocation::from_offset(source, offset)}
+}
+
+fn tokenize(text: &str) -> Result<Vec<Lexed>, DotError> {
+  let bytes = text.as_bytes();
+  let mut tokens = vec![];
+  let mut i = 0;
+  let at_line_start = |i: usize|
+    bytes[..i].iter().rev().take_while(|b| **b != b'\n').all(|b| *b == b' ' || *b == b'\t');
+
+  while i < bytes.len() {
+    let rest = &text[i..];
+    let c = rest.chars().next().unwrap();
+
+    if c.is_whitespace() {
+      i += c.len_utf8();
+    } else if rest.starts_with("//") || (c == '#' && at_line_start(i)) {
+      i += rest.find('\n').unwrap_or(rest.len());
+    } else if rest.starts_with("/*") {
+      match rest.find("*/") {
+        Some(end) => i += end + 2,
+        None => return Err(error(bytes, i, "unterminated comment"))
+      }
+    } else if rest.starts_with("->") || rest.starts_with("--") {
+      tokens.push((Token::EdgeOp, i));
+      i += 2;
+    } else if c == '"' {
+      let (value, len) = try!(quoted(rest).ok_or(error(bytes, i, "unterminated string")));
+      tokens.push((Token::Id(value, true), i));
+      i += len;
+    } else if c == '<' {
+      let len = try!(html_len(rest).ok_or(error(bytes, i, "unterminated HTML label")));
+      tokens.push((Token::Id(rest[1..len - 1].to_owned(), true), i));
+      i += len;
+    } else if "{}[]=;,:".contains(c) {
+      tokens.push((Token::Symbol(c), i));
+      i += 1;
+    } else if c.is_alphanumeric() || c == '_' || c == '.' || c == '-' {
+      // Names and numbers, such as node_1, 2.5 or -1. Edge operators were
+      // matched above, so a '-' here starts a negative number.
+      let len =
+        rest.char_indices().skip(1)
+          .find(|&(_, c)| !(c.is_alphanumeric() || c == '_' || c == '.'))
+          .map(|(end, _)| end)
+          .unwrap_or(rest.len());
+      tokens.push((Token::Id(rest[..len].to_owned(), false), i));
+      i += len;
+    } else {
+      return Err(error(bytes, i, &format!("unexpected character {:?}", c)));
+    }
+  }
+
+  Ok(tokens)
+}
+
+/// The contents of the quoted string at the start of `text` and the
+/// length of the string including its quotes. Escaped quotes are
+/// unescaped and every other escape is kept for the label to interpret.
+fn quoted(text: &str) -> Option<(String, usize)> {
+  let mut value = String::new();
+  let mut escaped = false;
+  for (i, c) in text.char_indices().skip(1) {
+    if escaped {
+      if c != '"' {
+        value.push('\\');
+      }
+      value.push(c);
+      escaped = false;
+    } else if c == '\\' {
+      escaped = true;
+    } else if c == '"' {
+      return Some((value, i + 1));
+    } else {
+      value.push(c);
+    }
+  }
+  None
+}
+
+/// Length of the `<...>` HTML string at the start of `text`, which may
+/// contain nested angle brackets.
+fn html_len(text: &str) -> Option<usize> {
+  let mut depth = 0;
+  for (i, c) in text.char_indices() {
+    if c == '<' {
+      depth += 1;
+    } else if c == '>' {
+      depth -= 1;
+      if depth == 0 {
+        return Some(i + 1);
+      }
+    }
+  }
+  None
+}
+
+type Attributes = HashMap<String, String>;
+
+struct Node {
+  id: String,
+  attrs: Attributes,
+  offset: usize
+}
+
+struct Edge {
+  from: String,
+  to: String,
+  attrs: Attributes,
+  offset: usize
+}
+
+struct Graph {
+  directed: bool,
+  nodes: Vec<Node>,
+  edges: Vec<Edge>
+}
+
+/// One side of an edge: a single node, or every node in a group
+enum Endpoint {
+  Node(String, usize),
+  Group(Vec<(String, usize)>)
+}
+
+struct GraphParser<'a> {
+  source: &'a [u8],
+  tokens: &'a [Lexed],
+  pos: usize
+}
+
+impl<'a> GraphParser<'a> {
+  fn parse_graph(&mut self) -> Result<Graph, DotError> {
+    if self.keyword("strict") {
+      self.pos += 1;
+    }
+    let directed =
+      if self.keyword("digraph") {
+        true
+      } else if self.keyword("graph") {
+        false
+      } else {
+        return Err(self.error("expected graph or digraph"));
+      };
+    self.pos += 1;
+    if let Some(&(Token::Id(..), _)) = self.tokens.get(self.pos) {
+      self.pos += 1;
+    }
+
+    let mut graph = Graph{directed: directed, nodes: vec![], edges: vec![]};
+    try!(self.expect('{'));
+    try!(self.parse_statements(&mut graph, &Attributes::new(), &Attributes::new()));
+    if self.pos < self.tokens.len() {
+      return Err(self.error("unexpected text after the graph"));
+    }
+    Ok(graph)
+  }
+
+  /// Parses statements up to and including the closing brace. Defaults
+  /// set by `node` and `edge` statements last until the end of the block.
+  fn parse_statements(
+    &mut self,
+    graph: &mut Graph,
+    node_defaults: &Attributes,
+    edge_defaults: &Attributes)
+      -> Result<(), DotError> {
+    let mut node_defaults = node_defaults.clone();
+    let mut edge_defaults = edge_defaults.clone();
+
+    loop {
+      match self.tokens.get(self.pos) {
+        None => return Err(self.error("expected }")),
+        Some(&(Token::Symbol('}'), _)) => {
+          self.pos += 1;
+          return Ok(());
+        }
+        Some(&(Token::Symbol(';'), _)) => self.pos += 1,
+        Some(&(Token::Symbol('{'), _)) =>
+          try!(self.parse_node_or_edge(graph, &node_defaults, &edge_defaults)),
+        Some(&(Token::Id(ref id, quoted), _)) => {
+          let lower = if quoted {String::new()} else {id.to_lowercase()};
+          if lower == "node" || lower == "edge" || lower == "graph" {
+            self.pos += 1;
+            let attrs = try!(self.parse_attributes());
+            let defaults = if lower == "node" {&mut node_defaults} else {&mut edge_defaults};
+            if lower != "graph" {
+              defaults.extend(attrs);
+            }
+          } else if let Some(&(Token::Symbol('='), _)) = self.tokens.get(self.pos + 1) {
+            // A graph attribute such as rankdir=LR
+            self.pos += 3;
+          } else {
+            try!(self.parse_node_or_edge(graph, &node_defaults, &edge_defaults));
+          }
+        }
+        Some(_) => return Err(self.error("expected a statement"))
+      }
+    }
+  }
+
+  fn parse_node_or_edge(
+    &mut self,
+    graph: &mut Graph,
+    node_defaults: &Attributes,
+    edge_defaults: &Attributes)
+      -> Result<(), DotError> {
+    let mut endpoints = vec![try!(self.parse_endpoint(graph, node_defaults, edge_defaults))];
+    while let Some(&(Token::EdgeOp, _)) = self.tokens.get(self.pos) {
+      self.pos += 1;
+      endpoints.push(try!(self.parse_endpoint(graph, node_defaults, edge_defaults)));
+    }
+    let attrs = try!(self.parse_attributes());
+
+    if endpoints.len() == 1 {
+      if let Some(Endpoint::Node(id, offset)) = endpoints.pop() {
+        let mut node_attrs = node_defaults.clone();
+        node_attrs.extend(attrs);
+        graph.add_node(Node{id: id, attrs: node_attrs, offset: offset});
+      }
+      return Ok(());
+    }
+
+    let sides: Vec<_> =
+      endpoints.into_iter().map(|endpoint| match endpoint {
+        Endpoint::Node(id, offset) => vec![(id, offset)],
+        Endpoint::Group(members) => members
+      }).collect();
+    let mut edge_attrs = edge_defaults.clone();
+    edge_attrs.extend(attrs);
+    for pair in sides.windows(2) {
+      for &(ref from, offset) in pair[0].iter() {
+        for &(ref to, _) in pair[1].iter() {
+          graph.edges.push(Edge{
+            from: from.clone(),
+            to: to.clone(),
+            attrs: edge_attrs.clone(),
+            offset: offset});
+        }
+      }
+    }
+    Ok(())
+  }
+
+  /// A node name, or a subgraph or `{...}` group. Nodes the graph doesn't
+  /// have yet and the statements in a group are added to the graph.
+  fn parse_endpoint(
+    &mut self,
+    graph: &mut Graph,
+    node_defaults: &Attributes,
+    edge_defaults: &Attributes)
+      -> Result<Endpoint, DotError> {
+    let is_group =
+      match self.tokens.get(self.pos) {
+        Some(&(Token::Symbol('{'), _)) => true,
+        _ => self.keyword("subgraph")
+      };
+    if !is_group {
+      let (id, offset) = try!(self.parse_node_id());
+      if !graph.nodes.iter().any(|n| n.id == id) {
+        graph.nodes.push(Node{id: id.clone(), attrs: node_defaults.clone(), offset: offset});
+      }
+      return Ok(Endpoint::Node(id, offset));
+    }
+
+    if self.keyword("subgraph") {
+      self.pos += 1;
+      if let Some(&(Token::Id(..), _)) = self.tokens.get(self.pos) {
+        self.pos += 1;
+      }
+    }
+    try!(self.expect('{'));
+    let mut group = Graph{directed: graph.directed, nodes: vec![], edges: vec![]};
+    try!(self.parse_statements(&mut group, node_defaults, edge_defaults));
+
+    let members = group.nodes.iter().map(|n| (n.id.clone(), n.offset)).collect();
+    for node in group.nodes.into_iter() {
+      graph.add_node(node);
+    }
+    graph.edges.extend(group.edges);
+    Ok(Endpoint::Group(members))
+  }
+
+  /// A node name, skipping any `:port` or `:compass` after it
+  fn parse_node_id(&mut self) -> Result<(String, usize), DotError> {
+    let id = match self.tokens.get(self.pos) {
+      Some(&(Token::Id(ref id, _), offset)) => (id.clone(), offset),
+      _ => return Err(self.error("expected a node name"))
+    };
+    self.pos += 1;
+    while let Some(&(Token::Symbol(':'), _)) = self.tokens.get(self.pos) {
+      self.pos += 1;
+      match self.tokens.get(self.pos) {
+        Some(&(Token::Id(..), _)) => self.pos += 1,
+        _ => return Err(self.error("expected a port name"))
+      }
+    }
+    Ok(id)
+  }
+
+  /// Any number of `[name=value, ...]` lists, merged together
+  fn parse_attributes(&mut self) -> Result<Attributes, DotError> {
+    let mut attrs = Attributes::new();
+    while let Some(&(Token::Symbol('['), _)) = self.tokens.get(self.pos) {
+      self.pos += 1;
+      loop {
+        match self.tokens.get(self.pos) {
+          Some(&(Token::Symbol(']'), _)) => {
+            self.pos += 1;
+            break;
+          }
+          Some(&(Token::Symbol(','), _)) | Some(&(Token::Symbol(';'), _)) => self.pos += 1,
+          Some(&(Token::Id(ref name, _), _)) => {
+            self.pos += 1;
+            try!(self.expect('='));
+            match self.tokens.get(self.pos) {
+              Some(&(Token::Id(ref value, _), _)) => {
+                attrs.insert(name.to_lowercase(), value.clone());
+                self.pos += 1;
+              }
+              _ => return Err(self.error("expected an attribute value"))
+            }
+          }
+          _ => return Err(self.error("expected an attribute or ]"))
+        }
+      }
+    }
+    Ok(attrs)
+  }
+
+  fn keyword(&self, word: &str) -> bool {
+    match self.tokens.get(self.pos) {
+      Some(&(Token::Id(ref id, false), _)) => id.to_lowercase() == word,
+      _ => false
+    }
+  }
+
+  fn expect(&mut self, symbol: char) -> Result<(), DotError> {
+    match self.tokens.get(self.pos) {
+      Some(&(Token::Symbol(s), _)) if s == symbol => {
+        self.pos += 1;
+        Ok(())
+      }
+      _ => Err(self.error(&format!("expected {}", symbol)))
+    }
+  }
+
+  fn error(&self, message: &str) -> DotError {
+    let offset = self.tokens.get(self.pos).map(|t| t.1).unwrap_or(self.source.len());
+    error(self.source, offset, message)
+  }
+}
+
+impl Graph {
+  /// Adds a node, or adds its attributes to the node with the same name
+  fn add_node(&mut self, node: Node) {
+    match self.nodes.iter().position(|n| n.id == node.id) {
+      Some(existing) => self.nodes[existing].attrs.extend(node.attrs),
+      None => self.nodes.push(node)
+    }
+  }
+
+  fn to_specs(&self, source: &[u8]) -> Vec<LocatedSpec> {
+    let mut names: HashMap<&str, String> = HashMap::new();
+    let mut specs = vec![];
+
+    for node in self.nodes.iter() {
+      let name = unique_name(&node.id, names.values().map(|n| &n[..]).collect());
+      names.insert(&node.id, name.clone());
+
+      let text =
+        match node.attrs.get("label") {
+          Some(label) if !label.trim().is_empty() => label_text(label, &node.id),
+          _ => node.id.clone()
+        };
+      let color = node.attrs.get("color").map(|c| color_by_name(c)).unwrap_or(Coloring::Default);
+      specs.push((
+        SourceLocation::from_offset(source, node.offset),
+        DataSpec::BlockDataSpec(BlockSpec::Boxed(name, color, text))));
+    }
+
+    for edge in self.edges.iter() {
+      let dir = edge.attrs.get("dir").map(|d| d.to_lowercase()).unwrap_or(
+        if self.directed {"forward".to_owned()} else {"none".to_owned()});
+      let (ty, from, to) =
+        match &dir[..] {
+          "both" => (ConnectionType::Dual, &edge.from, &edge.to),
+          "back" => (ConnectionType::Singular, &edge.to, &edge.from),
+          "forward" => (ConnectionType::Singular, &edge.from, &edge.to),
+          _ => (ConnectionType::Generic, &edge.from, &edge.to)
+        };
+      let label =
+        edge.attrs.get("label")
+          .map(|l| label_text(l, "").replace('\n', " ").replace('"', "'"))
+          .and_then(|l| if l.trim().is_empty() {None} else {Some(l.trim().to_owned())});
+
+      specs.push((
+        SourceLocation::from_offset(source, edge.offset),
+        DataSpec::ConnectionDataSpec(Connection{
+          ty: ty,
+          start: names[&from[..]].clone(),
+          end: names[&to[..]].clone(),
+          color: edge.attrs.get("color").map(|c| color_by_name(c)).unwrap_or(Coloring::Default),
+          label: label})));
+    }
+
+    specs
+  }
+}
+
+/// A block name for a node name read from another format, made of its
+/// letters and digits and numbered if another node already has that name.
+pub fn unique_name(id: &str, taken: Vec<&str>) -> String {
+  let base:String = id.chars().filter(|c| (*c as u32) < 128 && c.is_alphanumeric()).collect();
+  let base = if base.is_empty() {"node".to_owned()} else {base};
+  if !taken.contains(&&base[..]) {
+    return base;
+  }
+  (2..).map(|n| format!("{}{}", base, n)).find(|name| !taken.contains(&&name[..])).unwrap()
+}
+
+/// Box text for a DOT label. `\n`, `\l` and `\r` end lines, `\N` is the
+/// node name and any other escaped character stands for itself.
+fn label_text(label: &str, node: &str) -> String {
+  let mut text = String::new();
+  let mut chars = label.chars();
+  while let Some(c) = chars.next() {
+    if c != '\\' {
+      text.push(c);
+      continue;
+    }
+    match chars.next() {
+      Some('n') | Some('l') | Some('r') => text.push('\n'),
+      Some('N') => text.push_str(node),
+      Some(other) => text.push(other),
+      None => ()
+    }
+  }
+  text.trim_right_matches('\n').to_owned()
+}
+
+/// The closest cog colour to a colour name, which may come from DOT or
+/// CSS as they share these names. Colours cog has no equivalent for use
+/// the default.
+pub fn color_by_name(color: &str) -> Coloring {
+  match &color.trim().to_lowercase()[..] {
+    "black" => Coloring::Black,
+    "white" | "gray" | "grey" | "silver" | "lightgray" | "lightgrey" => Coloring::White,
+    "red" | "darkred" | "firebrick" | "crimson" => Coloring::Red,
+    "green" | "darkgreen" | "forestgreen" | "limegreen" => Coloring::Green,
+    "yellow" | "gold" | "orange" => Coloring::Yellow,
+    "blue" | "darkblue" | "navy" | "royalblue" => Coloring::Blue,
+    "magenta" | "purple" | "violet" | "fuchsia" => Coloring::Magenta,
+    "cyan" | "darkcyan" | "aqua" | "turquoise" => Coloring::Cyan,
+    _ => Coloring::Default
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::parse_dot;
+  use data::*;
+  use data::fixtures::*;
+
+  fn specs(source: &str) -> Vec<DataSpec> {
+    parse_dot(source.as_bytes()).unwrap().into_iter().map(|(_, spec)| spec).collect()
+  }
+
+  #[test]
+  fn reads_digraph() {
+    let source = r#"
+      // The web tier
+      digraph web {
+        rankdir=LR;
+        node [color=blue];
+        lb [label="Load\nbalancer"];
+        "app-1" [color=red]
+        lb -> "app-1" -> db [color=green];
+        db -> lb [dir=both]
+        cache -> "app-1" [dir=none];
+      }"#;
+    assert_eq!(specs(source), vec![
+      block("lb", Coloring::Blue, "Load\nbalancer"),
+      block("app1", Coloring::Red, "app-1"),
+      block("db", Coloring::Blue, "db"),
+      block("cache", Coloring::Blue, "cache"),
+      conn(ConnectionType::Singular, "lb", "app1", Coloring::Green, None),
+      conn(ConnectionType::Singular, "app1", "db", Coloring::Green, None),
+      conn(ConnectionType::Dual, "db", "lb", Coloring::Default, None),
+      conn(ConnectionType::Generic, "cache", "app1", Coloring::Default, None)]);
+  }
+
+  #[test]
+  fn reads_undirected_graph_with_subgraph() {
+    let source = "graph { subgraph cluster_a { a; b } a -- b [dir=back, label=\"uplink\"]; c--d }";
+    let found = specs(source);
+    assert_eq!(found.len(), 6);
+    assert_eq!(found[4], DataSpec::ConnectionDataSpec(Connection{
+      ty: ConnectionType::Singular,
+      start: "b".to_owned(),
+      end: "a".to_owned(),
+      color: Coloring::Default,
+      label: Some("uplink".to_owned())}));
+    assert_eq!(found[5], conn(ConnectionType::Generic, "c", "d", Coloring::Default, None));
+  }
+
+  #[test]
+  fn node_groups_connect_every_member() {
+    let source = "digraph { a -> {b c} [color=red]; {d; e} -> a:n; subgraph s { f } -> g:p1:sw }";
+    let found = specs(source);
+    assert_eq!(&found[..7], &[
+      block("a", Coloring::Default, "a"),
+      block("b", Coloring::Default, "b"),
+      block("c", Coloring::Default, "c"),
+      block("d", Coloring::Default, "d"),
+      block("e", Coloring::Default, "e"),
+      block("f", Coloring::Default, "f"),
+      block("g", Coloring::Default, "g")][..]);
+    assert_eq!(&found[7..], &[
+      conn(ConnectionType::Singular, "a", "b", Coloring::Red, None),
+      conn(ConnectionType::Singular, "a", "c", Coloring::Red, None),
+      conn(ConnectionType::Singular, "d", "a", Coloring::Default, None),
+      conn(ConnectionType::Singular, "e", "a", Coloring::Default, None),
+      conn(ConnectionType::Singular, "f", "g", Coloring::Default, None)][..]);
+  }
+
+  #[test]
+  fn errors_have_locations() {
+    let error = parse_dot(b"digraph {\n  a -> [color=red]\n}").unwrap_err();
+    assert_eq!(error.message, "expected a node name");
+    assert_eq!((error.location.line, error.location.column), (2, 8));
+    let error = parse_dot(b"digraph {\n  a: -> b\n}").unwrap_err();
+    assert_eq!(error.message, "expected a port name");
+    assert_eq!((error.location.line, error.location.column), (2, 6));
+  }
+}
@@ -0,0 +1,4 @@
+//! Conversion between cog diagrams and Graphviz DOT.
+
+pub mod import;
+pub mod export;
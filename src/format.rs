@@ -0,0 +1,439 @@
+use data::*;
+use parser::driver::{parse_spanned_specification, ParserError};
+use writer;
+
+/// A piece of a formatted file
+#[derive(Debug)]
+enum Item {
+  /// One or more blank lines, which are kept as a single one
+  Blank,
+  /// Lines holding nothing but comments
+  Comment(Vec<String>),
+  Statement(Statement)
+}
+
+#[derive(Debug)]
+struct Statement {
+  spec: DataSpec,
+  /// Comments found on the statement's own lines, moved to the end of
+  /// its first line
+  trailing: Option<String>,
+  /// Comment lines between a box header and its text
+  inner: Vec<String>
+}
+
+/// Rewrites `.cog` source in its canonical form.
+///
+/// Box text is indented by two spaces, runs of connections are lined up
+/// in columns and default colours are left out. Blocks are separated
+/// from everything around them by a blank line, and any other blank
+/// lines are collapsed into one.
+///
+/// Comments are kept next to the statement they were next to. Comments
+/// within a statement's lines, including block comments between its
+/// words, are moved to the end of its first line.
+pub fn format_source(source: &[u8]) -> Result<String, ParserError> {
+  let specs = try!(parse_spanned_specification(source));
+
+  let mut items = vec![];
+  let mut next = 0;
+  for (span, spec) in specs.into_iter() {
+    let start = statement_start(source, span.start);
+    between_items(&String::from_utf8_lossy(&source[next..start]), &mut items);
+    let text = String::from_utf8_lossy(&source[start..span.end]);
+    items.push(Item::Statement(statement_item(&text, spec)));
+    next = after_line_ending(source, span.end);
+  }
+  between_items(&String::from_utf8_lossy(&source[next..]), &mut items);
+
+  Ok(write_items(&items))
+}
+
+/// Where the text of the statement whose first token is at `offset`
+/// begins. This is the start of its line, unless the line starts with
+/// the end of a comment begun on an earlier line.
+fn statement_start(source: &[u8], offset: usize) -> usize {
+  let line_start = source[..offset].iter().rposition(|b| *b == b'\n').map(|i| i + 1).unwrap_or(0);
+  let before = String::from_utf8_lossy(&source[line_start..offset]);
+  let before = before.trim_left();
+  if before.is_empty() || before.starts_with("/*") {line_start} else {offset}
+}
+
+/// Skips the line ending at `offset`, if there is one.
+fn after_line_ending(source: &[u8], offset: usize) -> usize {
+  let rest = &source[offset..];
+  if rest.starts_with(b"\r\n") {
+    offset + 2
+  } else if rest.starts_with(b"\n") {
+    offset + 1
+  } else {
+    offset
+  }
+}
+
+/// Turns the blank and comment lines between statements into items.
+fn between_items(text: &str, items: &mut Vec<Item>) {
+  let lines:Vec<&str> = text.lines().collect();
+  let mut i = 0;
+  while i < lines.len() {
+    if lines[i].trim().is_empty() {
+      items.push(Item::Blank);
+      i += 1;
+    } else {
+      let (comment, used) = comment_lines(&lines[i..]);
+      items.push(Item::Comment(comment));
+      i += used;
+    }
+  }
+}
+
+/// Takes the comment starting on the first line. Block comments can run
+/// over several lines, which are kept as they are apart from trailing
+/// whitespace.
+fn comment_lines(lines: &[&str]) -> (Vec<String>, usize) {
+  let first = lines[0].trim();
+  if first.starts_with("/*") && !first[2..].contains("*/") {
+    let mut comment = vec![first.to_owned()];
+    for line in lines[1..].iter() {
+      comment.push(line.trim_right().to_owned());
+      if line.contains("*/") {
+        break;
+      }
+    }
+    let used = comment.len();
+    (comment, used)
+  } else {
+    (vec![first.to_owned()], 1)
+  }
+}
+
+fn is_comment_line(line: &str) -> bool {
+  let line = line.trim_left();
+  line.starts_with("#") || line.starts_with("//") || line.starts_with("/*")
+}
+
+/// Builds the item for a statement from its text, which runs from the
+/// start of its first line to the end of any comments after it.
+fn statement_item(text: &str, spec: DataSpec) -> Statement {
+  if let DataSpec::ConnectionDataSpec(_) = spec {
+    return Statement{spec: spec, trailing: trailing_comments(text), inner: vec![]};
+  }
+
+  let header_len = header_end(text);
+  let mut trailing = trailing_comments(&text[..header_len]);
+  let mut inner = vec![];
+  let lines:Vec<&str> = text[header_len..].lines().skip(1).collect();
+
+  let mut i = 0;
+  while i < lines.len() && is_comment_line(lines[i]) {
+    let (comment, used) = comment_lines(&lines[i..]);
+    inner.extend(comment);
+    i += used;
+  }
+
+  if i < lines.len() && lines[i].trim_left().starts_with("\"\"\"") {
+    let body = lines[i + 1..].join("\n");
+    let after = body.splitn(2, "\"\"\"").nth(1).unwrap_or("").trim();
+    if !after.is_empty() {
+      trailing = Some(join_comments(trailing, after.to_owned()));
+    }
+  }
+
+  Statement{spec: spec, trailing: trailing, inner: inner}
+}
+
+/// Length of a box header, which ends at the first line break that
+/// isn't inside a block comment.
+fn header_end(text: &str) -> usize {
+  let mut i = 0;
+  while i < text.len() {
+    let rest = &text[i..];
+    if rest.starts_with("\n") || rest.starts_with("\r\n") {
+      return i;
+    } else if rest.starts_with("#") || rest.starts_with("//") {
+      return i + rest.find('\n').unwrap_or(rest.len());
+    } else if rest.starts_with("/*") {
+      i += rest.find("*/").map(|e| e + 2).unwrap_or(rest.len());
+    } else {
+      i += rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+    }
+  }
+  text.len()
+}
+
+/// Every comment in a statement's text, outside of quotes.
+fn trailing_comments(text: &str) -> Option<String> {
+  let mut comments = None;
+  let mut in_quotes = false;
+  let mut i = 0;
+
+  while i < text.len() {
+    let rest = &text[i..];
+    if rest.starts_with("\"") {
+      in_quotes = !in_quotes;
+    } else if !in_quotes && (rest.starts_with("#") || rest.starts_with("//")) {
+      return Some(join_comments(comments, rest.trim_right().to_owned()));
+    } else if !in_quotes && rest.starts_with("/*") {
+      let end = rest.find("*/").map(|e| e + 2).unwrap_or(rest.len());
+      comments = Some(join_comments(comments, rest[..end].to_owned()));
+      i += end;
+      continue;
+    }
+    i += rest.chars().next().map(|c| c.len_utf8()).unwrap_or(1);
+  }
+
+  comments
+}
+
+fn join_comments(first: Option<String>, next: String) -> String {
+  match first {
+    Some(first) => format!("{} {}", first, next),
+    None => next
+  }
+}
+
+fn write_items(items: &[Item]) -> String {
+  let mut out = String::new();
+  let mut i = 0;
+  // Whether the last thing written needs a blank line after it
+  // before another statement
+  let mut after_block = false;
+  let mut last_was_statement = false;
+
+  while i < items.len() {
+    match items[i] {
+      Item::Blank => {
+        let more = items[i..].iter().any(|item| if let Item::Blank = *item {false} else {true});
+        if !out.is_empty() && !out.ends_with("\n\n") && more {
+          out.push('\n');
+        }
+        i += 1;
+        continue;
+      }
+      Item::Comment(ref lines) => {
+        if after_block && !out.ends_with("\n\n") {
+          out.push('\n');
+        }
+        for line in lines.iter() {
+          out.push_str(line);
+          out.push('\n');
+        }
+        after_block = false;
+        last_was_statement = false;
+        i += 1;
+      }
+      Item::Statement(ref statement) => {
+        let is_block = if let DataSpec::BlockDataSpec(_) = statement.spec {true} else {false};
+        if (after_block || (is_block && last_was_statement)) && !out.ends_with("\n\n") {
+          out.push('\n');
+        }
+
+        if is_block {
+          write_block(&mut out, statement);
+          i += 1;
+        } else {
+          let run = connection_run(&items[i..]);
+          write_connections(&mut out, &run);
+          i += run.len();
+        }
+        after_block = is_block;
+        last_was_statement = true;
+      }
+    }
+  }
+
+  out
+}
+
+fn write_block(out: &mut String, statement: &Statement) {
+  if let DataSpec::BlockDataSpec(ref block) = statement.spec {
+    let BlockSpec::Boxed(_, _, ref text) = *block;
+    out.push_str(&writer::block_header(block));
+    for comment in statement.trailing.iter() {
+      out.push(' ');
+      out.push_str(comment);
+    }
+    out.push('\n');
+    for comment in statement.inner.iter() {
+      out.push_str("  ");
+      out.push_str(comment.trim_left());
+      out.push('\n');
+    }
+    writer::write_box_text(out, text);
+  }
+}
+
+/// The connections at the start of `items` with nothing between them
+fn connection_run(items: &[Item]) -> Vec<&Statement> {
+  items.iter().map(
+    |item| match *item {
+      Item::Statement(ref s) => match s.spec {
+        DataSpec::ConnectionDataSpec(_) => Some(s),
+        _ => None
+      },
+      _ => None
+    }
+  ).take_while(|s| s.is_some()).map(|s| s.unwrap()).collect()
+}
+
+/// Writes connections with each of their words lined up in columns, and
+/// any trailing comments lined up after them.
+fn write_connections(out: &mut String, run: &[&Statement]) {
+  let fields:Vec<Vec<String>> = run.iter().map(
+    |s| match s.spec {
+      DataSpec::ConnectionDataSpec(ref conn) => writer::connection_fields(conn),
+      _ => vec![]
+    }
+  ).collect();
+
+  let column_count = fields.iter().map(|f| f.len()).max().unwrap_or(0);
+  let widths:Vec<usize> = (0..column_count).map(
+    |c| fields.iter().map(|f| f[c].chars().count()).max().unwrap_or(0)
+  ).collect();
+
+  let lines:Vec<String> = fields.iter().map(
+    |f| {
+      let padded:Vec<String> =
+        f.iter().zip(widths.iter()).filter(|&(_, w)| *w > 0).map(
+          |(field, w)| format!("{:1$}", field, w)
+        ).collect();
+      padded.join(" ").trim_right().to_owned()
+    }
+  ).collect();
+  let line_width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+
+  for (line, statement) in lines.iter().zip(run.iter()) {
+    out.push_str(line);
+    for comment in statement.trailing.iter() {
+      let padding = line_width - line.chars().count() + 1;
+      for _ in 0..padding {
+        out.push(' ');
+      }
+      out.push_str(comment);
+    }
+    out.push('\n');
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::format_source;
+  use data::*;
+  use parser::driver::parse_specification;
+  use writer::to_source;
+
+  #[test]
+  fn formats_canonically() {
+    let source =
+      b"box   text a  color default\n    Alpha\n      beta\n\n\n\
+        box text b color red # the second one\n// about the text\nBravo\n\
+        generic connection a b color default\n\
+        singular  connection b a color blue label \"back\"  /* return */\n";
+    assert_eq!(
+      &format_source(source).unwrap()[..],
+      "box text a\n  Alpha\n  beta\n\n\
+       box text b color red # the second one\n  // about the text\n  Bravo\n\n\
+       generic  connection a b\n\
+       singular connection b a color blue label \"back\" /* return */\n");
+  }
+
+  #[test]
+  fn keeps_comments_in_place() {
+    let source =
+      b"# Header comment\n\nbox text a\n  A\n\n/* two\n   lines */\nbox text b\n  \"\"\"\n  B\n  \"\"\" # end\n\n\
+        // links\nconnection a b\n\nconnection b a\n# trailer\n";
+    assert_eq!(
+      &format_source(source).unwrap()[..],
+      "# Header comment\n\nbox text a\n  A\n\n/* two\n   lines */\nbox text b # end\n  B\n\n\
+       // links\ngeneric connection a b\n\ngeneric connection b a\n# trailer\n");
+  }
+
+  #[test]
+  fn keeps_comments_spanning_lines_within_statements() {
+    let source =
+      b"box text a /* about\n  a */ color red\n  A\n\n\
+        connection a /* x\n y */ b\nconnection b /* z */ a # done\n";
+    let formatted = format_source(source).unwrap();
+    assert_eq!(
+      &formatted[..],
+      "box text a color red /* about\n  a */\n  A\n\n\
+       generic connection a b /* x\n y */\n\
+       generic connection b a /* z */ # done\n");
+    assert_eq!(parse_specification(formatted.as_bytes()).unwrap(), parse_specification(source).unwrap());
+    assert_eq!(format_source(formatted.as_bytes()).unwrap(), formatted);
+  }
+
+  /// A small deterministic random number generator, so that the round
+  /// trip tests cover many specifications without extra dependencies.
+  struct Lcg(u64);
+
+  impl Lcg {
+    fn below(&mut self, n: usize) -> usize {
+      self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1442695040888963407);
+      ((self.0 >> 33) as usize) % n
+    }
+
+    fn pick<'a, T>(&mut self, options: &'a [T]) -> &'a T {
+      &options[self.below(options.len())]
+    }
+  }
+
+  const WORDS: [&'static str; 8] = ["alpha", "Beta", "#hash", "//", "/*", "x\"y", "10GbE", "\u{e9}t\u{e9}"];
+  const COLORS: [Coloring; 4] = [Coloring::Default, Coloring::Red, Coloring::Cyan, Coloring::White];
+  const TYPES: [ConnectionType; 3] = [ConnectionType::Generic, ConnectionType::Singular, ConnectionType::Dual];
+
+  /// Text of one to three lines, some of them indented. Sometimes every
+  /// line shares the same indentation.
+  fn random_text(rng: &mut Lcg) -> String {
+    let shared = if rng.below(4) == 0 {"  "} else {""};
+    let mut lines = vec![];
+    for i in 0..(1 + rng.below(3)) {
+      let indent = if i > 0 && rng.below(4) == 0 {"  "} else {""};
+      let words:Vec<&str> = (0..(1 + rng.below(3))).map(|_| *rng.pick(&WORDS)).collect();
+      lines.push(format!("{}{}{}", shared, indent, words.join(" ")));
+      if i > 0 && rng.below(5) == 0 {
+        lines.insert(i, String::new());
+      }
+    }
+    lines.join("\n")
+  }
+
+  fn random_specs(rng: &mut Lcg) -> Vec<DataSpec> {
+    let names:Vec<String> = (0..(1 + rng.below(4))).map(|i| format!("b{}", i)).collect();
+    let mut specs:Vec<DataSpec> = names.iter().map(
+      |name| DataSpec::BlockDataSpec(
+        BlockSpec::Boxed(name.clone(), *rng.pick(&COLORS), random_text(rng)))
+    ).collect();
+    for _ in 0..rng.below(5) {
+      specs.push(DataSpec::ConnectionDataSpec(Connection{
+        ty: *rng.pick(&TYPES),
+        start: rng.pick(&names).clone(),
+        end: rng.pick(&names).clone(),
+        color: *rng.pick(&COLORS),
+        label: if rng.below(2) == 0 {None} else {Some("link # 1".to_owned())}}));
+    }
+    specs
+  }
+
+  #[test]
+  fn written_source_parses_back() {
+    let mut rng = Lcg(7);
+    for _ in 0..200 {
+      let specs = random_specs(&mut rng);
+      let source = to_source(&specs);
+      assert_eq!(parse_specification(source.as_bytes()).unwrap(), specs);
+    }
+  }
+
+  #[test]
+  fn formatting_keeps_meaning_and_is_stable() {
+    let mut rng = Lcg(11);
+    for _ in 0..200 {
+      let specs = random_specs(&mut rng);
+      let source = to_source(&specs);
+      let formatted = format_source(source.as_bytes()).unwrap();
+      assert_eq!(parse_specification(formatted.as_bytes()).unwrap(), specs);
+      assert_eq!(format_source(formatted.as_bytes()).unwrap(), formatted);
+    }
+  }
+}
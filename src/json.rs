@@ -0,0 +1,309 @@
+use data::*;
+use parser::driver::LocatedSpec;
+use parser::location::SourceLocation;
+use rustc_serialize::{Decodable, Decoder, Encodable, Encoder};
+use rustc_serialize::json;
+use rustc_serialize::json::Json;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str;
+use writer::{color_name, connection_type_name};
+
+const ALL_COLORS: [Coloring; 9] = [
+  Coloring::Default,
+  Coloring::Black,
+  Coloring::White,
+  Coloring::Red,
+  Coloring::Green,
+  Coloring::Yellow,
+  Coloring::Blue,
+  Coloring::Magenta,
+  Coloring::Cyan];
+
+const ALL_CONNECTION_TYPES: [ConnectionType; 3] = [
+  ConnectionType::Singular,
+  ConnectionType::Dual,
+  ConnectionType::Generic];
+
+/// A problem reading specifications from JSON, and where it is.
+#[derive(Debug, PartialEq, Eq)]
+pub struct JsonError {
+  pub message: String,
+  pub location: SourceLocation
+}
+
+impl Display for JsonError {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    write!(f, "{} at {}\n{}", self.message, self.location, self.location.snippet())
+  }
+}
+
+impl Error for JsonError {
+  fn description(&self) -> &str {
+    &self.message
+  }
+}
+
+impl Encodable for Coloring {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    color_name(*self).encode(s)
+  }
+}
+
+impl Decodable for Coloring {
+  fn decode<D: Decoder>(d: &mut D) -> Result<Coloring, D::Error> {
+    let name = try!(d.read_str());
+    match color_by_name(&name) {
+      Some(color) => Ok(color),
+      None => Err(d.error(&format!("unknown color {}", name)))
+    }
+  }
+}
+
+impl Encodable for ConnectionType {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    connection_type_name(*self).encode(s)
+  }
+}
+
+impl Decodable for ConnectionType {
+  fn decode<D: Decoder>(d: &mut D) -> Result<ConnectionType, D::Error> {
+    let name = try!(d.read_str());
+    match connection_type_by_name(&name) {
+      Some(ty) => Ok(ty),
+      None => Err(d.error(&format!("unknown connection kind {}", name)))
+    }
+  }
+}
+
+impl Encodable for DataSpec {
+  fn encode<S: Encoder>(&self, s: &mut S) -> Result<(), S::Error> {
+    match *self {
+      DataSpec::BlockDataSpec(BlockSpec::Boxed(ref name, ref color, ref text)) =>
+        s.emit_struct("DataSpec", 4, |s| {
+          try!(s.emit_struct_field("type", 0, |s| "box".encode(s)));
+          try!(s.emit_struct_field("name", 1, |s| name.encode(s)));
+          try!(s.emit_struct_field("color", 2, |s| color.encode(s)));
+          s.emit_struct_field("text", 3, |s| text.encode(s))
+        }),
+      DataSpec::ConnectionDataSpec(ref conn) =>
+        s.emit_struct("DataSpec", 6, |s| {
+          try!(s.emit_struct_field("type", 0, |s| "connection".encode(s)));
+          try!(s.emit_struct_field("kind", 1, |s| conn.ty.encode(s)));
+          try!(s.emit_struct_field("start", 2, |s| conn.start.encode(s)));
+          try!(s.emit_struct_field("end", 3, |s| conn.end.encode(s)));
+          try!(s.emit_struct_field("color", 4, |s| conn.color.encode(s)));
+          s.emit_struct_field("label", 5, |s| conn.label.encode(s))
+        })
+    }
+  }
+}
+
+fn color_by_name(name: &str) -> Option<Coloring> {
+  ALL_COLORS.iter().cloned().find(|c| color_name(*c) == name)
+}
+
+fn connection_type_by_name(name: &str) -> Option<ConnectionType> {
+  ALL_CONNECTION_TYPES.iter().cloned().find(|t| connection_type_name(*t) == name)
+}
+
+/// Writes specifications as a JSON array.
+///
+/// Blocks are objects with `"type": "box"` and their `name`, `color` and
+/// `text`. Connections have `"type": "connection"`, a `kind` of
+/// `singular`, `dual` or `generic`, their `start` and `end` blocks, a
+/// `color` and a `label`, which is null when there is none.
+pub fn to_json(specs: &[DataSpec]) -> String {
+  format!("{}\n", json::as_pretty_json(&specs))
+}
+
+/// Reads specifications from JSON in the form `to_json` writes, without
+/// going through the `.cog` parser. Colours, connection labels and the
+/// connection kind may be left out, defaulting to the default colour, no
+/// label and a singular connection.
+///
+/// Each specification is located at the start of its item in the array,
+/// and problems with an item name its index.
+pub fn parse_json(source: &[u8]) -> Result<Vec<LocatedSpec>, JsonError> {
+  let error_at_start =
+    |message: String| JsonError{message: message, location: SourceLocation::from_offset(source, 0)};
+
+  let text =
+    try!(str::from_utf8(source).map_err(|_| error_at_start("JSON is not valid UTF-8".to_owned())));
+  let document = try!(Json::from_str(text).map_err(|e| syntax_error(source, text, e)));
+  let items =
+    try!(document.as_array().ok_or(
+      error_at_start("expected an array of blocks and connections".to_owned())));
+
+  let mut specs = vec![];
+  for ((i, item), offset) in items.iter().enumerate().zip(item_offsets(text)) {
+    let location = SourceLocation::from_offset(source, offset);
+    let spec =
+      try!(spec_from_json(item).map_err(
+        |e| JsonError{message: format!("item {}: {}", i, e), location: location.clone()}));
+    specs.push((location, spec));
+  }
+  Ok(specs)
+}
+
+/// Finds the offset where each item of the array in `text` starts.
+/// `text` must already have been parsed as a JSON array.
+fn item_offsets(text: &str) -> Vec<usize> {
+  let mut offsets = vec![];
+  let mut depth = 0;
+  let mut in_string = false;
+  let mut escaped = false;
+  let mut expect_item = false;
+  for (i, c) in text.char_indices() {
+    if in_string {
+      if escaped {
+        escaped = false;
+      } else if c == '\\' {
+        escaped = true;
+      } else if c == '"' {
+        in_string = false;
+      }
+      continue;
+    }
+    if depth == 1 && expect_item && !c.is_whitespace() && c != ']' {
+      offsets.push(i);
+      expect_item = false;
+    }
+    match c {
+      '"' => in_string = true,
+      '[' | '{' => {
+        depth += 1;
+        expect_item = depth == 1;
+      }
+      ']' | '}' => depth -= 1,
+      ',' => expect_item = depth == 1,
+      _ => {}
+    }
+  }
+  offsets
+}
+
+fn syntax_error(source: &[u8], text: &str, error: json::ParserError) -> JsonError {
+  match error {
+    json::ParserError::SyntaxError(code, line, column) => {
+      let line_start: usize = text.split('\n').take(line - 1).map(|l| l.len() + 1).sum();
+      let offset =
+        text[line_start..].char_indices().nth(column - 1).map(|(i, _)| line_start + i)
+          .unwrap_or(text.len());
+      JsonError{
+        message: json::error_str(code).to_owned(),
+        location: SourceLocation::from_offset(source, offset)}
+    }
+    json::ParserError::IoError(e) =>
+      JsonError{message: e.description().to_owned(), location: SourceLocation::from_offset(source, 0)}
+  }
+}
+
+fn spec_from_json(item: &Json) -> Result<DataSpec, String> {
+  let object = try!(item.as_object().ok_or("expected an object".to_owned()));
+  let string = |key: &str| -> Result<Option<&str>, String> {
+    match object.get(key) {
+      None | Some(&Json::Null) => Ok(None),
+      Some(&Json::String(ref s)) => Ok(Some(s)),
+      Some(_) => Err(format!("{} must be a string", key))
+    }
+  };
+  let required = |key: &str| -> Result<String, String> {
+    try!(string(key)).map(|s| s.to_owned()).ok_or(format!("missing {}", key))
+  };
+  let color =
+    match try!(string("color")) {
+      None => Coloring::Default,
+      Some(name) =>
+        try!(color_by_name(name).ok_or(format!("unknown color {}", name)))
+    };
+
+  match try!(string("type")) {
+    Some("box") =>
+      Ok(DataSpec::BlockDataSpec(BlockSpec::Boxed(
+        try!(required("name")),
+        color,
+        try!(required("text"))))),
+    Some("connection") => {
+      let ty =
+        match try!(string("kind")) {
+          None => ConnectionType::Singular,
+          Some(name) =>
+            try!(connection_type_by_name(name).ok_or(format!("unknown connection kind {}", name)))
+        };
+      Ok(DataSpec::ConnectionDataSpec(Connection{
+        ty: ty,
+        start: try!(required("start")),
+        end: try!(required("end")),
+        color: color,
+        label: try!(string("label")).map(|l| l.to_owned())}))
+    }
+    _ => Err("type must be box or connection".to_owned())
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{parse_json, to_json};
+  use data::*;
+
+  fn specs() -> Vec<DataSpec> {
+    vec![
+      DataSpec::BlockDataSpec(BlockSpec::Boxed("a".to_owned(), Coloring::Red, "First\nline".to_owned())),
+      DataSpec::BlockDataSpec(BlockSpec::Boxed("b".to_owned(), Coloring::Default, "Second".to_owned())),
+      DataSpec::ConnectionDataSpec(Connection{
+        ty: ConnectionType::Dual,
+        start: "a".to_owned(),
+        end: "b".to_owned(),
+        color: Coloring::Default,
+        label: Some("uplink".to_owned())})]
+  }
+
+  fn read(source: &str) -> Vec<DataSpec> {
+    parse_json(source.as_bytes()).unwrap().into_iter().map(|(_, spec)| spec).collect()
+  }
+
+  #[test]
+  fn reads_back_the_same() {
+    let json = to_json(&specs());
+    assert!(json.contains("\"type\": \"box\""));
+    assert!(json.contains("\"kind\": \"dual\""));
+    assert_eq!(read(&json), specs());
+  }
+
+  #[test]
+  fn optional_fields_have_defaults() {
+    let source = r#"[
+      {"type": "box", "name": "a", "text": "A"},
+      {"type": "connection", "start": "a", "end": "b"}
+    ]"#;
+    assert_eq!(read(source)[1], DataSpec::ConnectionDataSpec(Connection{
+      ty: ConnectionType::Singular,
+      start: "a".to_owned(),
+      end: "b".to_owned(),
+      color: Coloring::Default,
+      label: None}));
+  }
+
+  #[test]
+  fn errors() {
+    let error = parse_json(b"[\n  {\"type\": \"box\",, }\n]").unwrap_err();
+    assert_eq!(error.location.line, 2);
+    let error = parse_json(br#"[
+  {"type": "box", "name": "a", "text": "[A]"},
+  {"type": "box", "name": "b", "text": "B", "color": "teal"}
+]"#).unwrap_err();
+    assert_eq!(error.message, "item 1: unknown color teal");
+    assert_eq!((error.location.line, error.location.column), (3, 3));
+  }
+
+  #[test]
+  fn items_are_located_where_they_start() {
+    let source = br#"[{"type": "box", "name": "a,{\"", "text": "A"},
+ {"type": "connection", "start": "a", "end": "b"}]"#;
+    let locations: Vec<_> =
+      parse_json(source).unwrap().into_iter().map(|(loc, _)| (loc.line, loc.column)).collect();
+    assert_eq!(locations, vec![(1, 2), (2, 2)]);
+  }
+}
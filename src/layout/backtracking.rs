@@ -8,6 +8,7 @@ use astar::ReusableSearchProblem;
 use astar::astar;
 
 use super::path_conversion::conn_display_with_path;
+use super::labels::place_labels;
 
 use super::LayoutManager;
 
@@ -21,6 +22,7 @@ impl LayoutManager for BacktrackingDownwardLayout {
   fn determine_block_vector_layout<'a>(
     &self,
     blocks:&'a [BlockSpec],
+    _connections:&[Connection],
     constraint:&BlockConstraint)
       -> Vec<(&'a BlockSpec, BlockDisplay)> {
     let mut displays:Vec<(&'a BlockSpec, BlockDisplay)> =
@@ -54,11 +56,14 @@ impl LayoutManager for BacktrackingDownwardLayout {
     blocks: &[(&'a BlockSpec, BlockDisplay)],
     constraint:&LayoutConstraint)
       -> Vec<ConnectionDisplay> {
-    self.recursive_connection_determination(
-      connections,
-      blocks,
-      constraint,
-      vec![]).unwrap_or_else(|| vec![])
+    let mut paths =
+      self.recursive_connection_determination(
+        connections,
+        blocks,
+        constraint,
+        vec![]).unwrap_or_else(|| vec![]);
+    place_labels(&mut paths, blocks);
+    paths
   }
 }
 
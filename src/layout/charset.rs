@@ -0,0 +1,197 @@
+use super::display::BlockCorner;
+use super::display::BlockCorner::*;
+
+/// The characters used to draw blocks and connections.
+///
+/// `Ascii` is the default and is readable anywhere. `Unicode` uses box
+/// drawing characters and arrowheads, and `Rounded` is the same with
+/// rounded corners on blocks and bends.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum CharSet {
+  Ascii,
+  Unicode,
+  Rounded
+}
+
+/// The sides of a cell that lines leave it by.
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub struct Sides {
+  pub up: bool,
+  pub down: bool,
+  pub left: bool,
+  pub right: bool
+}
+
+impl Sides {
+  pub fn union(self, other: Sides) -> Sides {
+    Sides{
+      up: self.up || other.up,
+      down: self.down || other.down,
+      left: self.left || other.left,
+      right: self.right || other.right}
+  }
+
+  /// Whether the only lines are vertical
+  pub fn is_vertical(self) -> bool {
+    (self.up || self.down) && !(self.left || self.right)
+  }
+
+  /// Whether the only lines are horizontal
+  pub fn is_horizontal(self) -> bool {
+    (self.left || self.right) && !(self.up || self.down)
+  }
+}
+
+impl CharSet {
+  pub fn from_name(name: &str) -> Option<CharSet> {
+    match name {
+      "ascii" => Some(CharSet::Ascii),
+      "unicode" => Some(CharSet::Unicode),
+      "rounded" => Some(CharSet::Rounded),
+      _ => None
+    }
+  }
+
+  /// Character for a straight line, either along a connection or
+  /// along the edge of a block.
+  pub fn line(self, vertical: bool) -> char {
+    match (self, vertical) {
+      (CharSet::Ascii, true) => '|',
+      (CharSet::Ascii, false) => '-',
+      (_, true) => '│',
+      (_, false) => '─'
+    }
+  }
+
+  pub fn corner(self, corner: BlockCorner) -> char {
+    match (self, corner) {
+      (CharSet::Ascii, _) => '+',
+      (CharSet::Unicode, TopLeft) => '┌',
+      (CharSet::Unicode, TopRight) => '┐',
+      (CharSet::Unicode, BottomLeft) => '└',
+      (CharSet::Unicode, BottomRight) => '┘',
+      (CharSet::Rounded, TopLeft) => '╭',
+      (CharSet::Rounded, TopRight) => '╮',
+      (CharSet::Rounded, BottomLeft) => '╰',
+      (CharSet::Rounded, BottomRight) => '╯'
+    }
+  }
+
+  /// Character placed where one connection part ends and the next begins.
+  pub fn bend(self) -> char {
+    match self {
+      CharSet::Ascii => '+',
+      _ => '┼'
+    }
+  }
+
+  /// Character where a vertical line crosses a horizontal one without
+  /// joining it. ASCII hops over the horizontal line, and the others
+  /// run the vertical line through a gap in it.
+  pub fn hop(self) -> char {
+    match self {
+      CharSet::Ascii => ')',
+      _ => self.line(true)
+    }
+  }
+
+  /// Character placed at an end of a connection without an arrowhead.
+  pub fn endpoint(self) -> char {
+    match self {
+      CharSet::Ascii => '#',
+      _ => '●'
+    }
+  }
+
+  /// Arrowhead for a connection entering a block while moving by `change`.
+  pub fn arrow(self, change: (i8, i8)) -> char {
+    let ascii = self == CharSet::Ascii;
+    match change {
+      (-1, _) => if ascii {'<'} else {'◀'},
+      (1, _) => if ascii {'>'} else {'▶'},
+      (_, -1) => if ascii {'^'} else {'▲'},
+      _ => if ascii {'v'} else {'▼'}
+    }
+  }
+
+  /// Character joining lines that leave a cell by each of `sides`.
+  ///
+  /// ASCII only has `+` for anything that isn't a straight line.
+  pub fn junction(self, sides: Sides) -> char {
+    if sides.is_vertical() {
+      return self.line(true);
+    }
+    if sides.is_horizontal() {
+      return self.line(false);
+    }
+    let corner = |kind| if self == CharSet::Ascii {'+'} else {self.corner(kind)};
+    match (sides.up, sides.down, sides.left, sides.right) {
+      (true, true, true, true) => self.bend(),
+      (true, true, false, true) => if self == CharSet::Ascii {'+'} else {'├'},
+      (true, true, true, false) => if self == CharSet::Ascii {'+'} else {'┤'},
+      (false, true, true, true) => if self == CharSet::Ascii {'+'} else {'┬'},
+      (true, false, true, true) => if self == CharSet::Ascii {'+'} else {'┴'},
+      (false, true, _, true) => corner(TopLeft),
+      (false, true, true, _) => corner(TopRight),
+      (true, false, _, true) => corner(BottomLeft),
+      _ => corner(BottomRight)
+    }
+  }
+
+  /// Elbow joining a part moving by `incoming` to one moving by `outgoing`.
+  ///
+  /// None for ASCII, which uses `bend` for every bend, and when the parts
+  /// don't turn a corner.
+  pub fn elbow(self, incoming: (i8, i8), outgoing: (i8, i8)) -> Option<char> {
+    // The elbow joins the side the line came in from with the side it
+    // leaves by, so flip the incoming direction.
+    let sides = match ((-incoming.0, -incoming.1), outgoing) {
+      ((0, v), (h, 0)) | ((h, 0), (0, v)) if h != 0 && v != 0 => (h, v),
+      _ => return None
+    };
+    match (self, sides) {
+      (CharSet::Ascii, _) => None,
+      (CharSet::Unicode, (1, 1)) => Some('┌'),
+      (CharSet::Unicode, (-1, 1)) => Some('┐'),
+      (CharSet::Unicode, (1, _)) => Some('└'),
+      (CharSet::Unicode, _) => Some('┘'),
+      (CharSet::Rounded, (1, 1)) => Some('╭'),
+      (CharSet::Rounded, (-1, 1)) => Some('╮'),
+      (CharSet::Rounded, (1, _)) => Some('╰'),
+      (CharSet::Rounded, _) => Some('╯')
+    }
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::{CharSet, Sides};
+
+  #[test]
+  fn elbows_join_adjacent_sides() {
+    // Down then right leaves from the top of the bend to its right side
+    assert_eq!(CharSet::Unicode.elbow((0, 1), (1, 0)), Some('└'));
+    assert_eq!(CharSet::Unicode.elbow((1, 0), (0, 1)), Some('┐'));
+    assert_eq!(CharSet::Unicode.elbow((0, -1), (-1, 0)), Some('┐'));
+    assert_eq!(CharSet::Rounded.elbow((-1, 0), (0, -1)), Some('╰'));
+    assert_eq!(CharSet::Unicode.elbow((0, 1), (0, 1)), None);
+    assert_eq!(CharSet::Ascii.elbow((0, 1), (1, 0)), None);
+  }
+
+  #[test]
+  fn junctions() {
+    let tee = Sides{up: true, down: true, left: false, right: true};
+    assert_eq!(CharSet::Unicode.junction(tee), '├');
+    assert_eq!(CharSet::Ascii.junction(tee), '+');
+    let all = tee.union(Sides{left: true, ..Sides::default()});
+    assert_eq!(CharSet::Unicode.junction(all), '┼');
+    assert_eq!(CharSet::Rounded.junction(Sides{down: true, left: true, ..Sides::default()}), '╮');
+    assert_eq!(CharSet::Ascii.junction(Sides{up: true, ..Sides::default()}), '|');
+  }
+
+  #[test]
+  fn names() {
+    assert_eq!(CharSet::from_name("rounded"), Some(CharSet::Rounded));
+    assert_eq!(CharSet::from_name("fancy"), None);
+  }
+}
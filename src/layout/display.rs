@@ -4,7 +4,7 @@ use std::cmp::{min,max};
 use std::mem;
 use std::ops::Add;
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Hash, RustcEncodable, RustcDecodable)]
 pub struct Position{
   pub x: u32,
   pub y: u32,
@@ -18,13 +18,13 @@ impl Add for Position {
   }
 }
 
-#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Copy, Clone, RustcEncodable, RustcDecodable)]
 pub struct Size{
   pub width: u32,
   pub height: u32,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
 pub struct BlockDisplay {
   pub color: Coloring,
   pub content_lines: Vec<String>,
@@ -48,21 +48,31 @@ pub struct BlockCornerIter {
   current_corner: Option<BlockCorner>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
 pub struct ConnectionPart {
   pub start: Position,
   pub end: Position,
+}
 
-  pub internal_character: char,
+#[derive(Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
+pub struct ConnectionLabel {
+  pub text: String,
+  /// Position of the first character of the label.
+  /// None if the layout couldn't find room for it.
+  pub pos: Option<Position>,
 }
 
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, PartialEq, Eq, RustcEncodable, RustcDecodable)]
 pub struct ConnectionDisplay {
   pub parts: Vec<ConnectionPart>,
   pub color: Coloring,
-  pub part_end_char: char,
-  pub total_start_char: char,
-  pub total_end_char: char
+  /// Whether the connection has an arrowhead where it leaves its
+  /// start block, rather than a plain end point
+  pub start_arrow: bool,
+  /// Whether the connection has an arrowhead where it enters its
+  /// end block
+  pub end_arrow: bool,
+  pub label: Option<ConnectionLabel>
 }
 
 
@@ -108,53 +118,19 @@ impl BlockDisplay{
     test: &str,
     constraint: &BlockConstraint)
       -> BlockDisplay {
-    // Check if we can just use the string directly
-    // + 4 allows for space on either side, plus lines on sides
-    if test.len() < constraint.min_limited_width as usize + 4 {
-      return
-        BlockDisplay{
-          color: color,
-          content_lines: vec![test.to_owned()],
-          pos: Position{x: 0, y: 0},
-          //height is 3 for line + text line
-          size: Size{width: test.len() as u32 + 4, height: 3}};
-    }
-
-    //This currently ignores the limitation on being too tall,
-    //to make implementation easier.
-    //TODO make this properly expand horizontally if needed
-    let mut lines:Vec<String> = vec![];
-    let mut current_line = String::new();
-    let mut widest_line:u32 = 0;
-    for word in test.split(' ') {
-      if current_line.len() + word.len() + 5 > constraint.min_limited_width as usize{
-        let old_current = mem::replace(&mut current_line, String::new());
-        widest_line = max(old_current.len() as u32, widest_line);
-        lines.push(old_current);
-      }
-
-      if current_line.len() + 4 > constraint.min_limited_width as usize{
-        let old_current = mem::replace(&mut current_line, String::new());
-        widest_line = max(old_current.len() as u32, widest_line);
-        lines.push(old_current);
-      } else {
-        if current_line.len() > 0 {
-          current_line.push(' ');
-        }
-        current_line.push_str(word);
-      }
-    }
-
-    if current_line.len() > 0 {
-      widest_line = max(current_line.len() as u32, widest_line);
-      lines.push(current_line);
-    }
+    // Explicit line breaks are always kept, and only lines that are
+    // too long on their own get wrapped.
+    let lines:Vec<String> =
+      test.split('\n').flat_map(|line| wrap_line(line, constraint).into_iter()).collect();
+    let widest_line = lines.iter().map(|l| l.len() as u32).max().unwrap_or(0);
 
+    //height is lines plus a border line above and below
     let h = lines.len() + 2;
     BlockDisplay{
       color: color,
       content_lines: lines,
       pos: Position{x: 0, y: 0},
+      // + 4 allows for space on either side, plus lines on sides
       size: Size{width: widest_line + 4, height: h as u32}}
   }
 
@@ -174,6 +150,85 @@ impl BlockDisplay{
 }
 
 
+/// Splits a single line of block text by words so that it fits
+/// the width constraint. Lines that already fit are left alone.
+fn wrap_line(line: &str, constraint: &BlockConstraint) -> Vec<String> {
+  // Check if we can just use the string directly
+  // + 4 allows for space on either side, plus lines on sides
+  if line.len() < constraint.min_limited_width as usize + 4 {
+    return vec![line.to_owned()];
+  }
+
+  //This currently ignores the limitation on being too tall,
+  //to make implementation easier.
+  //TODO make this properly expand horizontally if needed
+  let mut lines:Vec<String> = vec![];
+  let mut current_line = String::new();
+  for word in line.split(' ') {
+    if current_line.len() + word.len() + 5 > constraint.min_limited_width as usize{
+      let old_current = mem::replace(&mut current_line, String::new());
+      lines.push(old_current);
+    }
+
+    if current_line.len() + 4 > constraint.min_limited_width as usize{
+      let old_current = mem::replace(&mut current_line, String::new());
+      lines.push(old_current);
+    } else {
+      if current_line.len() > 0 {
+        current_line.push(' ');
+      }
+      current_line.push_str(word);
+    }
+  }
+
+  if current_line.len() > 0 {
+    lines.push(current_line);
+  }
+
+  lines
+}
+
+impl ConnectionPart {
+  /// Number of steps from the start of the part to its end
+  pub fn length(&self) -> u32 {
+    self.start.manhattan_distance(self.end)
+  }
+
+  /// Whether the part runs up or down rather than across
+  pub fn is_vertical(&self) -> bool {
+    self.start.x == self.end.x
+  }
+
+  /// The change in x and y for each step along the part
+  pub fn direction(&self) -> (i8, i8) {
+    fn sign(from: u32, to: u32) -> i8 {
+      if to > from {
+        1
+      } else if to < from {
+        -1
+      } else {
+        0
+      }
+    }
+    (sign(self.start.x, self.end.x), sign(self.start.y, self.end.y))
+  }
+
+  /// Every position covered by the part, including both ends
+  pub fn positions(&self) -> Vec<Position> {
+    let (low, high) =
+      if self.start.x + self.start.y <= self.end.x + self.end.y {
+        (self.start, self.end)
+      } else {
+        (self.end, self.start)
+      };
+    if low.x == high.x {
+      (low.y..high.y + 1).map(|y| Position{x: low.x, y: y}).collect()
+    } else {
+      (low.x..high.x + 1).map(|x| Position{x: x, y: low.y}).collect()
+    }
+  }
+}
+
 //Collision functions
 impl BlockDisplay {
   pub fn distance_to_position(&self, pos:Position) -> u32 {
@@ -238,7 +293,24 @@ impl BlockCornerIter {
 #[cfg(test)]
 mod test {
   use super::*;
-  use data::Coloring;
+  use data::{BlockSpec, Coloring};
+  use layout::constraint::BlockConstraint;
+
+  #[test]
+  fn test_block_text_keeps_line_breaks(){
+    let constraint = BlockConstraint{
+      min_limited_width: 10,
+      max_height_per_width: 1,
+      max_width_per_height: 10,
+      inter_block_distance: 5};
+    let spec = BlockSpec::Boxed(
+      "a".to_owned(),
+      Coloring::Default,
+      "Title\nports one two three".to_owned());
+    let display = BlockDisplay::create_unpositioned_from_spec(&spec, &constraint);
+    assert_eq!(display.content_lines, vec!["Title", "ports", "one", "two", "three"]);
+    assert_eq!(display.size, Size{width: 9, height: 7});
+  }
 
   #[test]
   fn test_block_display_center(){
@@ -8,6 +8,7 @@ use astar::ReusableSearchProblem;
 use astar::astar;
 
 use super::path_conversion::conn_display_with_path;
+use super::labels::place_labels;
 
 use super::LayoutManager;
 
@@ -21,6 +22,7 @@ impl LayoutManager for CrossingDownwardLayout {
   fn determine_block_vector_layout<'a>(
     &self,
     blocks:&'a [BlockSpec],
+    _connections:&[Connection],
     constraint:&BlockConstraint)
       -> Vec<(&'a BlockSpec, BlockDisplay)> {
     let mut displays:Vec<(&'a BlockSpec, BlockDisplay)> =
@@ -72,7 +74,7 @@ impl LayoutManager for CrossingDownwardLayout {
         let end_conns_opt = open_connectors.get(&conn.end[..]);
 
         if start_conns_opt.is_none() || end_conns_opt.is_none() {
-          break;
+          continue;
         }
 
         let start_conns:Vec<Position> =
@@ -127,6 +129,7 @@ impl LayoutManager for CrossingDownwardLayout {
       paths.push(conn_display_with_path(conn, result));
     }
 
+    place_labels(&mut paths, blocks);
     paths
   }
 }
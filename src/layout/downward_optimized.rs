@@ -1,6 +1,6 @@
 use super::constraint::{LayoutConstraint,BlockConstraint, ConnectionConstraint};
-use super::display::{Position, ConnectionDisplay, BlockDisplay, ConnectionPart};
-use data::{Connection, BlockSpec, ConnectionType};
+use super::display::{Position, ConnectionDisplay, BlockDisplay};
+use data::{Connection, BlockSpec};
 use std::collections::{VecDeque,HashMap};
 use std::cmp::max;
 
@@ -9,6 +9,8 @@ use astar::astar;
 
 use super::LayoutManager;
 use super::memoizer::{PathCreator, PathMemoizer};
+use super::labels::place_labels;
+use super::path_conversion::conn_display_with_path;
 
 pub struct MemoizingDownwardLayout {
   pub screen_width: u32,
@@ -19,6 +21,7 @@ impl LayoutManager for MemoizingDownwardLayout {
   fn determine_block_vector_layout<'a>(
     &self,
     blocks:&'a [BlockSpec],
+    _connections:&[Connection],
     constraint:&BlockConstraint)
       -> Vec<(&'a BlockSpec, BlockDisplay)> {
     let mut displays:Vec<(&'a BlockSpec, BlockDisplay)> =
@@ -52,12 +55,15 @@ impl LayoutManager for MemoizingDownwardLayout {
     blocks: &[(&'a BlockSpec, BlockDisplay)],
     constraint:&LayoutConstraint)
       -> Vec<ConnectionDisplay> {
-    self.recursive_connection_determination(
-      connections,
-      blocks,
-      constraint,
-      vec![],
-      &mut PathMemoizer::new()).unwrap_or_else(|| vec![])
+    let mut paths =
+      self.recursive_connection_determination(
+        connections,
+        blocks,
+        constraint,
+        vec![],
+        &mut PathMemoizer::new()).unwrap_or_else(|| vec![]);
+    place_labels(&mut paths, blocks);
+    paths
   }
 }
 
@@ -176,7 +182,7 @@ impl MemoizingDownwardLayout {
               memoizer);
           match lower_result {
             Some(mut vals) => {
-              vals.push(conn_display_with_path(conn, &path));
+              vals.push(conn_display_with_path(conn, path.clone()));
               return Some(vals)
             }
             None => {
@@ -246,111 +252,6 @@ fn find_block_display<'a>(
   None
 }
 
-fn conn_display_with_path(conn: &Connection, path: &VecDeque<Position>) -> ConnectionDisplay {
-  let mut last_change:Option<(i8, i8)> = None;
-  let mut first_change:Option<(i8, i8)> = None;
-  let mut part_vec:Vec<ConnectionPart> = vec![];
-
-  let mut part_start:Option<Position> = None;
-  let mut last_point:Option<Position> = None;
-
-  for slice in path.into_iter().collect::<Vec<_>>().windows(2) {
-    let (a,b) = (slice[0], slice[1]);
-    if (a.x != b.x) && (a.y != b.y) {
-      panic!("How'd we get a path that moves diagonally!");
-    }
-
-    if part_start.is_none() {
-      part_start = Some(*a);
-    }
-    let new_change =
-      if a.x == b.x {
-        if a.y > b.y {
-          assert!(a.y - b.y == 1);
-          (0, -1)
-        } else {
-          assert!(b.y - a.y == 1);
-          (0, 1)
-        }
-      } else {
-        if a.x > b.x {
-          assert!(a.x - b.x == 1);
-          (-1, 0)
-        } else {
-          assert!(b.x - a.x == 1);
-          (1, 0)
-        }
-      };
-
-    if let Some(old_change) = last_change {
-      if new_change != old_change {
-        let part_char = if old_change.0 == 0 {'|'} else {'-'};
-        part_vec.push(
-          ConnectionPart{
-            start: part_start.unwrap(),
-            end: *a,
-            internal_character: part_char});
-        part_start = Some(*a);
-      }
-    }
-
-    last_change = Some(new_change);
-    if first_change.is_none() {
-      first_change = Some(new_change);
-    }
-    last_point = Some(*b);
-  }
-
-  if part_start.is_some() && last_point.is_some() && last_change.is_some(){
-    let part_char = if last_change.unwrap().0 == 0 {'|'} else {'-'};
-    part_vec.push(
-      ConnectionPart{
-        start: part_start.unwrap(),
-        end: last_point.unwrap(),
-        internal_character: part_char});
-  }
-
-  let (total_start,total_end) =
-    total_chars(
-      first_change.map(|fc| (-fc.0, -fc.1)),
-      last_change,
-      conn.ty);
-
-  ConnectionDisplay{
-    parts:part_vec,
-    color:conn.color,
-    part_end_char: '+',
-    total_start_char: total_start,
-    total_end_char: total_end
-  }
-}
-
-fn total_chars(first_change:Option<(i8, i8)>, last_change:Option<(i8,i8)>, ty:ConnectionType) -> (char,char) {
-  if ty == ConnectionType::Generic {
-    ('#', '#')
-  } else if ty == ConnectionType::Singular {
-    ('#', incoming_for_change(last_change))
-  } else {
-    (incoming_for_change(first_change), incoming_for_change(last_change))
-  }
-}
-
-fn incoming_for_change(change:Option<(i8, i8)>) -> char {
-  if let Some((x,y)) = change {
-    if x == -1 {
-      '<'
-    } else if x == 1 {
-      '>'
-    } else if y == -1 {
-      '^'
-    } else {
-      'v'
-    }
-  } else {
-    '#'
-  }
-}
-
 struct DisplayNodeFinder<'a, 'b, 'c> {
   blocks: Vec<&'a BlockDisplay>,
   non_checked_blocks: Vec<&'a BlockDisplay>,
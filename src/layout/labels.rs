@@ -0,0 +1,118 @@
+use data::BlockSpec;
+use super::display::{BlockDisplay, ConnectionDisplay, Position};
+
+/// Finds a position for the label of each connection that has one.
+///
+/// Labels go beside the longest straight part of their connection that
+/// has room for them, so that they don't cover blocks, other connections
+/// or labels that were already placed. Labels that don't fit anywhere are
+/// left without a position.
+pub fn place_labels(
+  connections: &mut [ConnectionDisplay],
+  blocks: &[(&BlockSpec, BlockDisplay)]) {
+  let mut taken: Vec<Position> =
+    connections.iter()
+      .flat_map(|c| c.parts.iter())
+      .flat_map(|p| p.positions().into_iter())
+      .collect();
+
+  for conn in connections.iter_mut() {
+    let pos = {
+      let text_len = match conn.label {
+        Some(ref label) => label.text.chars().count() as u32,
+        None => continue
+      };
+
+      let mut parts:Vec<_> = conn.parts.iter().collect();
+      parts.sort_by(|a, b| b.length().cmp(&a.length()));
+
+      parts.iter()
+        .flat_map(|p| label_candidates(p.start, p.end, text_len).into_iter())
+        .filter(|c| is_free(*c, text_len, &taken, blocks))
+        .next()
+    };
+
+    if let Some(p) = pos {
+      let label = conn.label.as_mut().unwrap();
+      for i in 0..label.text.chars().count() as u32 {
+        taken.push(p.add_x(i));
+      }
+      label.pos = Some(p);
+    }
+  }
+}
+
+/// Start positions to try for a label beside a straight part, best first.
+/// Horizontal parts get the label centred above or below them, vertical
+/// parts get it to the right or left of their middle.
+fn label_candidates(start: Position, end: Position, text_len: u32) -> Vec<Position> {
+  let mid = Position{x: (start.x + end.x) / 2, y: (start.y + end.y) / 2};
+  let mut candidates = vec![];
+
+  if start.y == end.y {
+    if mid.x >= text_len / 2 {
+      let x = mid.x - text_len / 2;
+      if mid.y > 0 {
+        candidates.push(Position{x: x, y: mid.y - 1});
+      }
+      candidates.push(Position{x: x, y: mid.y + 1});
+    }
+  } else {
+    candidates.push(Position{x: mid.x + 2, y: mid.y});
+    if mid.x >= text_len + 2 {
+      candidates.push(Position{x: mid.x - text_len - 1, y: mid.y});
+    }
+  }
+
+  candidates
+}
+
+fn is_free(
+  start: Position,
+  text_len: u32,
+  taken: &[Position],
+  blocks: &[(&BlockSpec, BlockDisplay)])
+    -> bool {
+  (0..text_len).map(|i| start.add_x(i)).all(
+    |p| !taken.contains(&p) &&
+        blocks.iter().all(|&(_, ref b)| b.distance_to_position(p) > 0))
+}
+
+#[cfg(test)]
+mod test {
+  use super::place_labels;
+  use data::{BlockSpec, Coloring};
+  use layout::display::*;
+
+  fn labelled(parts: Vec<ConnectionPart>) -> ConnectionDisplay {
+    ConnectionDisplay{
+      parts: parts,
+      color: Coloring::Default,
+      start_arrow: false,
+      end_arrow: false,
+      label: Some(ConnectionLabel{text: "gRPC".to_owned(), pos: None})}
+  }
+
+  #[test]
+  fn label_goes_beside_longest_part() {
+    let mut conns = vec![labelled(vec![
+      ConnectionPart{start: Position{x:10, y:0}, end: Position{x:10, y:2}},
+      ConnectionPart{start: Position{x:10, y:2}, end: Position{x:30, y:2}}])];
+    place_labels(&mut conns, &[]);
+    assert_eq!(conns[0].label.as_ref().unwrap().pos, Some(Position{x:18, y:1}));
+  }
+
+  #[test]
+  fn label_avoids_blocks() {
+    let spec = BlockSpec::Boxed("a".to_owned(), Coloring::Default, "a".to_owned());
+    let block = BlockDisplay{
+      color: Coloring::Default,
+      content_lines: vec!["a".to_owned()],
+      pos: Position{x:10, y:0},
+      size: Size{width:10, height:3}};
+    let mut conns = vec![labelled(vec![
+      ConnectionPart{start: Position{x:10, y:3}, end: Position{x:20, y:3}}])];
+    place_labels(&mut conns, &[(&spec, block)]);
+    assert_eq!(conns[0].label.as_ref().unwrap().pos, Some(Position{x:13, y:4}));
+  }
+}
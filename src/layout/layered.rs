@@ -0,0 +1,300 @@
+use super::constraint::{LayoutConstraint, BlockConstraint};
+use super::display::{BlockDisplay, ConnectionDisplay};
+use super::downward_cross::CrossingDownwardLayout;
+use data::{Connection, BlockSpec};
+use std::cmp::max;
+
+use super::LayoutManager;
+
+/// Number of ordering passes made over the layers when reducing crossings
+const ORDERING_SWEEPS: usize = 4;
+
+/// Lays blocks out in layers, in the style of a Sugiyama layout.
+///
+/// Every connection points from the layer of its start block towards
+/// the layer of its end block, so a block is always placed below the
+/// blocks that connect to it. Blocks in a layer are placed side by side,
+/// ordered to keep connections between neighbouring layers from crossing.
+/// Layers wider than `max_width` are wrapped onto several rows.
+///
+/// Connections are routed the same way as `CrossingDownwardLayout`.
+pub struct LayeredLayout {
+  pub max_width: u32
+}
+
+impl LayoutManager for LayeredLayout {
+  fn determine_block_vector_layout<'a>(
+    &self,
+    blocks:&'a [BlockSpec],
+    connections:&[Connection],
+    constraint:&BlockConstraint)
+      -> Vec<(&'a BlockSpec, BlockDisplay)> {
+    let mut displays:Vec<(&'a BlockSpec, BlockDisplay)> =
+      blocks.iter().map(
+        |bspec| {
+          (bspec, BlockDisplay::create_unpositioned_from_spec(bspec, constraint))
+        }).collect();
+
+    let edges = remove_cycles(blocks.len(), &build_edges(blocks, connections));
+    let layer_of = assign_layers(blocks.len(), &edges);
+    let layers = order_layers(group_layers(&layer_of), &edges);
+
+    let spacing = constraint.inter_block_distance;
+    let mut next_y = spacing;
+
+    for layer in layers.iter() {
+      for row in self.split_rows(layer, &displays, spacing).iter() {
+        let row_width =
+          row.iter().map(|i| displays[*i].1.size.width).sum::<u32>() +
+          spacing * (row.len() as u32 - 1);
+        let mut x = max(spacing, self.max_width.saturating_sub(row_width) / 2);
+        let mut row_height = 0;
+
+        for i in row.iter() {
+          let display = &mut displays[*i].1;
+          display.pos.x = x;
+          display.pos.y = next_y;
+          x += display.size.width + spacing;
+          row_height = max(row_height, display.size.height);
+        }
+
+        next_y += row_height + spacing;
+      }
+    }
+
+    displays
+  }
+
+  fn determine_connection_layout<'a>(
+    &self,
+    connections:&[Connection],
+    blocks: &[(&'a BlockSpec, BlockDisplay)],
+    constraint:&LayoutConstraint)
+      -> Vec<ConnectionDisplay> {
+    let router = CrossingDownwardLayout{screen_width: self.max_width, screen_height: constraint.max_height};
+    router.determine_connection_layout(connections, blocks, constraint)
+  }
+}
+
+impl LayeredLayout {
+  /// Splits an ordered layer into rows that each fit in `max_width`.
+  /// A block that is wider than `max_width` by itself gets its own row.
+  fn split_rows(
+    &self,
+    layer: &[usize],
+    displays: &[(&BlockSpec, BlockDisplay)],
+    spacing: u32)
+      -> Vec<Vec<usize>> {
+    let mut rows:Vec<Vec<usize>> = vec![];
+    let mut current:Vec<usize> = vec![];
+    let mut current_width = spacing;
+
+    for i in layer.iter() {
+      let width = displays[*i].1.size.width + spacing;
+      if !current.is_empty() && current_width + width > self.max_width {
+        rows.push(current);
+        current = vec![];
+        current_width = spacing;
+      }
+      current.push(*i);
+      current_width += width;
+    }
+
+    if !current.is_empty() {
+      rows.push(current);
+    }
+    rows
+  }
+}
+
+/// Directed edges between block indices for each connection. Connections
+/// to unknown blocks and from a block to itself don't affect layering.
+fn build_edges(blocks: &[BlockSpec], connections: &[Connection]) -> Vec<(usize, usize)> {
+  let index_of = |name: &str| blocks.iter().position(|b| b.get_name() == name);
+  let mut edges = vec![];
+
+  for conn in connections.iter() {
+    if let (Some(s), Some(e)) = (index_of(&conn.start), index_of(&conn.end)) {
+      if s != e && !edges.contains(&(s, e)) {
+        edges.push((s, e));
+      }
+    }
+  }
+
+  edges
+}
+
+/// Reverses every edge that closes a cycle, found by a depth first
+/// search in file order, so that the graph can be layered.
+fn remove_cycles(node_count: usize, edges: &[(usize, usize)]) -> Vec<(usize, usize)> {
+  fn visit(
+    node: usize,
+    edges: &[(usize, usize)],
+    on_stack: &mut Vec<bool>,
+    visited: &mut Vec<bool>,
+    result: &mut Vec<(usize, usize)>) {
+    visited[node] = true;
+    on_stack[node] = true;
+    for &(s, e) in edges.iter().filter(|edge| edge.0 == node) {
+      if on_stack[e] {
+        result.push((e, s));
+      } else {
+        result.push((s, e));
+        if !visited[e] {
+          visit(e, edges, on_stack, visited, result);
+        }
+      }
+    }
+    on_stack[node] = false;
+  }
+
+  let mut on_stack = vec![false; node_count];
+  let mut visited = vec![false; node_count];
+  let mut result = vec![];
+  for node in 0..node_count {
+    if !visited[node] {
+      visit(node, edges, &mut on_stack, &mut visited, &mut result);
+    }
+  }
+  result
+}
+
+/// Puts every block one layer below the lowest block connecting to it.
+/// Blocks with no incoming connections are in the top layer.
+fn assign_layers(node_count: usize, edges: &[(usize, usize)]) -> Vec<usize> {
+  let mut layer_of = vec![0; node_count];
+  let mut incoming:Vec<usize> =
+    (0..node_count).map(|n| edges.iter().filter(|e| e.1 == n).count()).collect();
+  let mut ready:Vec<usize> = (0..node_count).filter(|n| incoming[*n] == 0).collect();
+
+  while !ready.is_empty() {
+    let node = ready.remove(0);
+    for &(_, e) in edges.iter().filter(|edge| edge.0 == node) {
+      layer_of[e] = max(layer_of[e], layer_of[node] + 1);
+      incoming[e] -= 1;
+      if incoming[e] == 0 {
+        ready.push(e);
+      }
+    }
+  }
+
+  layer_of
+}
+
+fn group_layers(layer_of: &[usize]) -> Vec<Vec<usize>> {
+  let layer_count = layer_of.iter().map(|l| l + 1).max().unwrap_or(0);
+  (0..layer_count).map(
+    |layer| (0..layer_of.len()).filter(|n| layer_of[*n] == layer).collect()
+  ).collect()
+}
+
+/// Reorders blocks within each layer using the barycenter heuristic,
+/// sweeping down and then up through the layers.
+fn order_layers(mut layers: Vec<Vec<usize>>, edges: &[(usize, usize)]) -> Vec<Vec<usize>> {
+  for _ in 0..ORDERING_SWEEPS {
+    for i in 1..layers.len() {
+      let reordered = by_barycenter(&layers[i], &layers[i - 1], edges);
+      layers[i] = reordered;
+    }
+    for i in (0..layers.len().saturating_sub(1)).rev() {
+      let reordered = by_barycenter(&layers[i], &layers[i + 1], edges);
+      layers[i] = reordered;
+    }
+  }
+  layers
+}
+
+/// Sorts `layer` by the average position of each block's neighbours in
+/// `fixed`. Blocks without neighbours there keep their current position.
+fn by_barycenter(layer: &[usize], fixed: &[usize], edges: &[(usize, usize)]) -> Vec<usize> {
+  let mut keyed:Vec<(f32, usize)> = layer.iter().enumerate().map(
+    |(current, node)| {
+      let neighbours:Vec<usize> =
+        edges.iter().filter_map(
+          |&(s, e)|
+            if s == *node {
+              Some(e)
+            } else if e == *node {
+              Some(s)
+            } else {
+              None
+            }
+        ).filter_map(|n| fixed.iter().position(|f| *f == n)).collect();
+
+      let key =
+        if neighbours.is_empty() {
+          current as f32
+        } else {
+          neighbours.iter().sum::<usize>() as f32 / neighbours.len() as f32
+        };
+      (key, *node)
+    }
+  ).collect();
+
+  keyed.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+  keyed.into_iter().map(|(_, node)| node).collect()
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use data::{BlockSpec, Coloring, ConnectionType};
+  use data::fixtures::{boxed, connection};
+  use layout::LayoutManager;
+  use layout::constraint::BlockConstraint;
+
+  fn constraint() -> BlockConstraint {
+    BlockConstraint{
+      min_limited_width: 40,
+      max_height_per_width: 1,
+      max_width_per_height: 10,
+      inter_block_distance: 5}
+  }
+
+  #[test]
+  fn layers_follow_connections() {
+    assert_eq!(assign_layers(4, &[(0, 1), (1, 2), (0, 2)]), vec![0, 1, 2, 0]);
+  }
+
+  #[test]
+  fn cycles_are_broken() {
+    let edges = remove_cycles(3, &[(0, 1), (1, 2), (2, 0)]);
+    assert_eq!(edges, vec![(0, 1), (1, 2), (0, 2)]);
+    assert_eq!(assign_layers(3, &edges), vec![0, 1, 2]);
+  }
+
+  #[test]
+  fn barycenter_uncrosses_edges() {
+    let layers = order_layers(vec![vec![0, 1], vec![2, 3]], &[(0, 3), (1, 2)]);
+    assert_eq!(layers, vec![vec![0, 1], vec![3, 2]]);
+  }
+
+  #[test]
+  fn siblings_share_a_row() {
+    let blocks:Vec<BlockSpec> =
+      ["root", "left", "right"].iter().map(|n| boxed(n, Coloring::Default, n)).collect();
+    let layout = LayeredLayout{max_width: 100};
+    let connections = vec![
+      connection(ConnectionType::Singular, "root", "left", Coloring::Default, None),
+      connection(ConnectionType::Singular, "root", "right", Coloring::Default, None)];
+    let displays = layout.determine_block_vector_layout(&blocks, &connections, &constraint());
+
+    assert_eq!(displays[1].1.pos.y, displays[2].1.pos.y);
+    assert!(displays[0].1.pos.y + displays[0].1.size.height + 5 <= displays[1].1.pos.y);
+    assert_eq!(displays[1].1.pos.x + displays[1].1.size.width + 5, displays[2].1.pos.x);
+  }
+
+  #[test]
+  fn wide_layers_wrap() {
+    let blocks:Vec<BlockSpec> =
+      ["aaaaaaaaaa", "bbbbbbbbbb", "cccccccccc"].iter().map(|n| boxed(n, Coloring::Default, n)).collect();
+    let layout = LayeredLayout{max_width: 50};
+    let displays = layout.determine_block_vector_layout(&blocks, &[], &constraint());
+
+    assert_eq!(displays[0].1.pos.y, displays[1].1.pos.y);
+    assert!(displays[2].1.pos.y > displays[1].1.pos.y);
+    for &(_, ref d) in displays.iter() {
+      assert!(d.pos.x + d.size.width <= 50);
+    }
+  }
+}
@@ -1,13 +1,18 @@
 pub mod constraint;
 pub mod display;
+pub mod charset;
 pub mod backtracking;
 pub mod memoizer;
 pub mod downward_optimized;
 pub mod downward_cross;
 pub mod path_conversion;
+pub mod labels;
+pub mod layered;
+pub mod registry;
 
 use self::constraint::{LayoutConstraint,BlockConstraint, ConnectionConstraint};
-use self::display::{Position, ConnectionDisplay, BlockDisplay, ConnectionPart};
+use self::display::{Position, ConnectionDisplay, BlockDisplay, ConnectionPart, ConnectionLabel};
+use self::labels::place_labels;
 use data::{Connection, BlockSpec};
 use std::collections::{VecDeque,HashMap};
 use std::cmp::max;
@@ -19,6 +24,7 @@ pub trait LayoutManager{
   fn determine_block_vector_layout<'a>(
     &self,
     blocks: &'a [BlockSpec],
+    connections: &[Connection],
     constraint: &BlockConstraint)
       -> Vec<(&'a BlockSpec, BlockDisplay)>;
 
@@ -39,6 +45,7 @@ impl LayoutManager for DownwardLayout {
   fn determine_block_vector_layout<'a>(
     &self,
     blocks:&'a [BlockSpec],
+    _connections:&[Connection],
     constraint:&BlockConstraint)
       -> Vec<(&'a BlockSpec, BlockDisplay)> {
     let mut displays:Vec<(&'a BlockSpec, BlockDisplay)> =
@@ -90,7 +97,7 @@ impl LayoutManager for DownwardLayout {
         let end_conns_opt = open_connectors.get(&conn.end[..]);
 
         if start_conns_opt.is_none() || end_conns_opt.is_none() {
-          break;
+          continue;
         }
 
         let start_conns = start_conns_opt.unwrap();
@@ -143,6 +150,7 @@ impl LayoutManager for DownwardLayout {
       paths.push(conn_display_with_path(conn, result));
     }
 
+    place_labels(&mut paths, blocks);
     paths
   }
 }
@@ -217,12 +225,7 @@ fn conn_display_with_path(conn: &Connection, mut path: VecDeque<Position>) -> Co
 
     if let Some(old_change) = last_change {
       if new_change != old_change {
-        let part_char = if old_change.0 == 0 {'|'} else {'-'};
-        part_vec.push(
-          ConnectionPart{
-            start: part_start.unwrap(),
-            end: a,
-            internal_character: part_char});
+        part_vec.push(ConnectionPart{start: part_start.unwrap(), end: a});
         part_start = Some(a);
       }
     }
@@ -232,20 +235,15 @@ fn conn_display_with_path(conn: &Connection, mut path: VecDeque<Position>) -> Co
   }
 
   if part_start.is_some() && last_point.is_some() && last_change.is_some(){
-    let part_char = if last_change.unwrap().0 == 0 {'|'} else {'-'};
-    part_vec.push(
-      ConnectionPart{
-        start: part_start.unwrap(),
-        end: last_point.unwrap(),
-        internal_character: part_char});
+    part_vec.push(ConnectionPart{start: part_start.unwrap(), end: last_point.unwrap()});
   }
 
   ConnectionDisplay{
     parts:part_vec,
     color:conn.color,
-    part_end_char: '+',
-    total_start_char: '#',
-    total_end_char: '#'
+    start_arrow: false,
+    end_arrow: false,
+    label: conn.label.as_ref().map(|text| ConnectionLabel{text: text.clone(), pos: None})
   }
 }
 
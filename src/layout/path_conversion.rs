@@ -1,4 +1,4 @@
-use super::display::{ConnectionDisplay, Position, ConnectionPart};
+use super::display::{ConnectionDisplay, Position, ConnectionPart, ConnectionLabel};
 use data::{Connection, ConnectionType};
 use std::collections::VecDeque;
 
@@ -6,11 +6,13 @@ use std::collections::VecDeque;
 /// ConnectionDisplay.
 ///
 /// This breaks the path down into it's constituent parts (straight lines)
-/// and uses information from the connection to figure out what colors and
-/// characters to use when rendering it.
-pub fn conn_display_with_path(conn: &Connection, mut path: VecDeque<Position>) -> ConnectionDisplay {
+/// and uses information from the connection to figure out its color and
+/// which of its ends have arrowheads. The characters it is drawn with are
+/// left to the renderer.
+pub fn conn_display_with_path(
+  conn: &Connection,
+  mut path: VecDeque<Position>) -> ConnectionDisplay {
   let mut last_change:Option<(i8, i8)> = None;
-  let mut first_change:Option<(i8, i8)> = None;
   let mut part_vec:Vec<ConnectionPart> = vec![];
 
   let mut part_start:Option<Position> = None;
@@ -53,12 +55,7 @@ pub fn conn_display_with_path(conn: &Connection, mut path: VecDeque<Position>) -
       // If the change before the current one was different,
       // we need to create a new connection part
       if new_change != old_change {
-        let part_char = if old_change.0 == 0 {'|'} else {'-'};
-        part_vec.push(
-          ConnectionPart{
-            start: part_start.unwrap(),
-            end: a,
-            internal_character: part_char});
+        part_vec.push(ConnectionPart{start: part_start.unwrap(), end: a});
         //The start of the next part is whatever change we just saw
         //that was different from the last part
         part_start = Some(a);
@@ -68,70 +65,20 @@ pub fn conn_display_with_path(conn: &Connection, mut path: VecDeque<Position>) -
     // Set the last change to be the one we just dealt with
     last_change = Some(new_change);
 
-    // No changes yet - so set up the first one
-    if first_change.is_none() {
-      first_change = Some(new_change);
-    }
-
     //Record the last point so we can use it for the next iteration
     last_point = Some(b);
   }
 
   //If we have a part in progress, finish it
   if part_start.is_some() && last_point.is_some() && last_change.is_some(){
-    let part_char = if last_change.unwrap().0 == 0 {'|'} else {'-'};
-    part_vec.push(
-      ConnectionPart{
-        start: part_start.unwrap(),
-        end: last_point.unwrap(),
-        internal_character: part_char});
+    part_vec.push(ConnectionPart{start: part_start.unwrap(), end: last_point.unwrap()});
   }
 
-  // Figure out the characters for the end points
-  let (total_start,total_end) =
-    total_chars(
-      first_change.map(|fc| (-fc.0, -fc.1)),
-      last_change,
-      conn.ty);
-
   ConnectionDisplay{
     parts:part_vec,
     color:conn.color,
-    part_end_char: '+',
-    total_start_char: total_start,
-    total_end_char: total_end
-  }
-}
-
-/// Figure out what characters to place on the end of a connection.
-///
-/// * `first_change` - The change in x and y values for the first step of the path
-/// * `last_change` - The change in x and y values for the last step of the path
-/// * `ty` - the type of connection that is being used
-fn total_chars(first_change:Option<(i8, i8)>, last_change:Option<(i8,i8)>, ty:ConnectionType) -> (char,char) {
-  if ty == ConnectionType::Generic {
-    ('#', '#')
-  } else if ty == ConnectionType::Singular {
-    ('#', incoming_for_change(last_change))
-  } else {
-    (incoming_for_change(first_change), incoming_for_change(last_change))
-  }
-}
-
-/// Determines what character to use for a position change
-/// when entering a block
-fn incoming_for_change(change:Option<(i8, i8)>) -> char {
-  if let Some((x,y)) = change {
-    if x == -1 {
-      '<'
-    } else if x == 1 {
-      '>'
-    } else if y == -1 {
-      '^'
-    } else {
-      'v'
-    }
-  } else {
-    '#'
+    start_arrow: conn.ty == ConnectionType::Dual,
+    end_arrow: conn.ty != ConnectionType::Generic,
+    label: conn.label.as_ref().map(|text| ConnectionLabel{text: text.clone(), pos: None})
   }
 }
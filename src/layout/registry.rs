@@ -0,0 +1,102 @@
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+use super::LayoutManager;
+use super::DownwardLayout;
+use super::downward_cross::CrossingDownwardLayout;
+use super::downward_optimized::MemoizingDownwardLayout;
+use super::backtracking::BacktrackingDownwardLayout;
+use super::layered::LayeredLayout;
+
+/// The layout used when none is asked for
+pub const DEFAULT_LAYOUT: &'static str = "crossing";
+
+/// Everything a layout manager might need to be created
+pub struct LayoutOptions {
+  pub screen_width: u32,
+  pub screen_height: u32,
+  pub max_width: u32
+}
+
+type LayoutConstructor = fn(&LayoutOptions) -> Box<LayoutManager>;
+
+static LAYOUTS: [(&'static str, LayoutConstructor); 5] = [
+  ("crossing", crossing_layout),
+  ("downward", downward_layout),
+  ("memoizing", memoizing_layout),
+  ("backtracking", backtracking_layout),
+  ("layered", layered_layout)];
+
+#[derive(Debug, PartialEq, Eq)]
+pub struct UnknownLayout(pub String);
+
+impl Display for UnknownLayout {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    write!(f, "unknown layout {}, expected one of: {}", self.0, layout_names().join(", "))
+  }
+}
+
+impl Error for UnknownLayout {
+  fn description(&self) -> &str {
+    "unknown layout name"
+  }
+}
+
+/// Names of every layout that can be created with `create_layout_manager`
+pub fn layout_names() -> Vec<&'static str> {
+  LAYOUTS.iter().map(|&(name, _)| name).collect()
+}
+
+pub fn create_layout_manager(name: &str, options: &LayoutOptions)
+    -> Result<Box<LayoutManager>, UnknownLayout> {
+  match LAYOUTS.iter().find(|&&(n, _)| n == name) {
+    Some(&(_, constructor)) => Ok(constructor(options)),
+    None => Err(UnknownLayout(name.to_owned()))
+  }
+}
+
+fn crossing_layout(options: &LayoutOptions) -> Box<LayoutManager> {
+  Box::new(CrossingDownwardLayout{
+    screen_width: options.screen_width,
+    screen_height: options.screen_height})
+}
+
+fn downward_layout(options: &LayoutOptions) -> Box<LayoutManager> {
+  Box::new(DownwardLayout{
+    screen_width: options.screen_width,
+    screen_height: options.screen_height})
+}
+
+fn memoizing_layout(options: &LayoutOptions) -> Box<LayoutManager> {
+  Box::new(MemoizingDownwardLayout{
+    screen_width: options.screen_width,
+    screen_height: options.screen_height})
+}
+
+fn backtracking_layout(options: &LayoutOptions) -> Box<LayoutManager> {
+  Box::new(BacktrackingDownwardLayout{
+    screen_width: options.screen_width,
+    screen_height: options.screen_height})
+}
+
+fn layered_layout(options: &LayoutOptions) -> Box<LayoutManager> {
+  Box::new(LayeredLayout{max_width: options.max_width})
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+
+  #[test]
+  fn unknown_layout_lists_names() {
+    let options = LayoutOptions{screen_width: 50, screen_height: 50, max_width: 150};
+    match create_layout_manager("sideways", &options) {
+      Err(e) => assert_eq!(
+        format!("{}", e),
+        "unknown layout sideways, expected one of: crossing, downward, memoizing, backtracking, layered"),
+      Ok(_) => panic!("Expected an unknown layout")
+    }
+    assert!(create_layout_manager(DEFAULT_LAYOUT, &options).is_ok());
+  }
+}
@@ -0,0 +1,37 @@
+//! Cog lays out and draws box and arrow diagrams.
+//!
+//! A diagram is read from its `.cog` source by a `ParserDriver` into a
+//! list of `DataSpec`s, placed by a `LayoutManager`, and finally drawn by
+//! one of the renderers in `render`.
+
+#![feature(collections)]
+#![feature(iter_arith)]
+#![feature(iter_cmp)]
+#![feature(convert)]
+
+extern crate ncurses;
+extern crate collections;
+extern crate astar;
+extern crate rustc_serialize;
+extern crate libc;
+
+#[macro_use]
+extern crate nom;
+
+pub mod parser;
+pub mod data;
+pub mod layout;
+pub mod render;
+pub mod validation;
+pub mod config;
+pub mod writer;
+pub mod format;
+pub mod builder;
+pub mod dot;
+pub mod mermaid;
+pub mod json;
+
+pub use builder::DiagramBuilder;
+pub use data::{BlockSpec, Coloring, Connection, ConnectionType, DataSpec};
+pub use layout::LayoutManager;
+pub use parser::driver::{FileDriver, ParserDriver, ParserError, ReaderDriver, StrDriver};
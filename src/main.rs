@@ -1,78 +1,222 @@
-#![feature(collections)]
 #![feature(plugin)]
-#![feature(iter_arith)]
-#![feature(iter_cmp)]
 #![feature(convert)]
 #![plugin(docopt_macros)]
 
+extern crate cog;
 extern crate rustc_serialize;
 extern crate ncurses;
-extern crate collections;
 extern crate docopt;
-extern crate astar;
 
-#[macro_use]
-extern crate nom;
-
-use parser::driver::ParserDriver;
+use cog::{config, dot, format, json, mermaid, render, validation, writer};
+use cog::parser::driver::{parse_located_specification, FileDriver, LocatedSpec, ParserDriver, ReaderDriver};
 use std::error::Error;
-use layout::constraint::*;
-use layout::{LayoutManager};
-use layout::downward_cross::CrossingDownwardLayout;
-use layout::display::Position;
-use data::{Connection, DataSpec, BlockSpec};
-
-mod parser;
-mod data;
-mod layout;
-mod render;
+use std::fmt::Display;
+use std::io;
+use std::io::Write;
+use std::fs::File;
+use std::process;
+use std::path::{Path, PathBuf};
+use cog::layout::LayoutManager;
+use cog::layout::registry::{create_layout_manager, LayoutOptions};
+use cog::layout::charset::CharSet;
+use cog::layout::display::{BlockDisplay, ConnectionDisplay, Size};
+use cog::render::viewer::{view_until_changed, Viewport};
+use cog::data::{Connection, DataSpec, BlockSpec};
 
 docopt!(Args derive Debug, "
-Usage: cog [options] <infile>
+Usage:
+  cog [options] <infile>
+  cog fmt [--write] <infile>
+
+Use - as the input file to read the diagram from stdin. The ncurses view needs
+stdin for the keyboard, so choose another --output then. Files ending in .dot
+or .gv are read as Graphviz DOT, .mmd or .mermaid as a Mermaid flowchart,
+.json as the JSON that --emit json writes and anything else as cog source.
 
 Options:
-");
+  -o, --output <format>  Output format: ncurses, text, ansi, html, svg, or
+                         json for the computed layout. ansi is text coloured
+                         for terminals, plain unless stdout is a terminal
+                         and NO_COLOR is unset. [default: ncurses]
+  --from <format>        Input format: cog, dot, mermaid or json.
+  --emit <format>        Print the diagram as cog, dot, mermaid or json
+                         source instead of laying it out.
+  --check                Check the diagram for undefined or duplicate block
+                         names and self-loops, then exit. Fails if any are found.
+  --config <file>        Read settings from this file instead of the cog.conf
+                         found in the input file's directory or its parents.
+  -l, --layout <name>    Layout algorithm: crossing, downward, memoizing,
+                         backtracking or layered. Defaults to crossing.
+  --charset <name>       Characters to draw with: ascii, unicode or rounded.
+                         Defaults to ascii.
+  -w, --write            With fmt, rewrite the file in place instead of
+                         printing the formatted source.
+  --watch                Keep the ncurses view open, reading the file again
+                         and redrawing the diagram whenever it changes.
+
+Layout settings (these override the configuration file):
+  --min-block-width <n>        Width at which block text starts to wrap.
+  --max-height-per-width <n>   Maximum block height per unit of width.
+  --max-width-per-height <n>   Maximum block width per unit of height.
+  --block-spacing <n>          Space between blocks.
+  --min-connection-length <n>  Minimum length of a connection.
+  --max-connection-length <n>  Maximum length of a connection.
+  --box-distance <n>           Distance connections keep from other blocks.
+  --max-width <n>              Maximum width of the diagram.
+  --max-height <n>             Maximum height of the diagram.
+  --screen-width <n>           Width of the screen the diagram is centred on.
+  --screen-height <n>          Height of the screen the diagram is drawn on.
+",
+flag_from: Option<String>,
+flag_emit: Option<String>,
+flag_config: Option<String>,
+flag_layout: Option<String>,
+flag_charset: Option<String>,
+flag_min_block_width: Option<String>,
+flag_max_height_per_width: Option<String>,
+flag_max_width_per_height: Option<String>,
+flag_block_spacing: Option<String>,
+flag_min_connection_length: Option<String>,
+flag_max_connection_length: Option<String>,
+flag_box_distance: Option<String>,
+flag_max_width: Option<String>,
+flag_max_height: Option<String>,
+flag_screen_width: Option<String>,
+flag_screen_height: Option<String>);
+
+/// Values --output accepts
+const OUTPUT_FORMATS: [&'static str; 6] = ["ncurses", "text", "ansi", "html", "svg", "json"];
+/// Values --emit accepts, in any case
+const EMIT_FORMATS: [&'static str; 4] = ["cog", "dot", "mermaid", "json"];
 
 fn main() {
   let cli_args: Args = Args::docopt().decode().unwrap_or_else(|e| e.exit());
 
-  let spec_reader = parser::driver::FileDriver::new(&cli_args.arg_infile);
+  if cli_args.cmd_fmt {
+    format_file(&cli_args.arg_infile, cli_args.flag_write);
+    return;
+  }
 
-  let specs =
-    match spec_reader {
-      Ok(fdriver) => fdriver.read_to_specification(),
-      Err(e) => {
-        println!("Could not read {}: {}", cli_args.arg_infile, e.description());
-        return;
-      }
-    };
+  if cli_args.flag_watch {
+    if cli_args.flag_check || cli_args.flag_emit.is_some() || cli_args.flag_output != "ncurses" {
+      fail("--watch only works with the ncurses view, not with --check, --emit or another --output");
+    }
+    match load_settings(&cli_args) {
+      Ok(settings) => watch(&cli_args, &settings),
+      Err(e) => fail(e)
+    }
+    return;
+  }
 
-  let constraint = BlockConstraint {
-    min_limited_width: 40,
-    max_height_per_width: 1,
-    max_width_per_height: 10,
-    inter_block_distance: 5
+  if !OUTPUT_FORMATS.contains(&&cli_args.flag_output[..]) {
+    fail(format!("Unknown output format {}", cli_args.flag_output));
+  }
+  for format in cli_args.flag_emit.iter() {
+    if !EMIT_FORMATS.contains(&&format.to_lowercase()[..]) {
+      fail(format!("Unknown source format {}", format));
+    }
+  }
+  let shows_ncurses =
+    cli_args.flag_output == "ncurses" && !cli_args.flag_check && cli_args.flag_emit.is_none();
+  if shows_ncurses && cli_args.arg_infile == "-" {
+    fail("The ncurses view reads keys from stdin, so it can't show a diagram read from stdin");
+  }
+
+  let source = match read_source(&cli_args.arg_infile) {
+    Ok(source) => source,
+    Err(e) => fail(format!("Could not read {}: {}", cli_args.arg_infile, e.description()))
   };
-  let spec_ok = match specs {
-    Ok(res) => res,
-    Err(e) => {println!("{:?}", e); return;}
+
+  let located_specs =
+    match read_specs(&source, &input_format(&cli_args.arg_infile, &cli_args.flag_from)) {
+      Ok(res) => res,
+      Err(e) => fail(e)
+    };
+
+  // The ncurses view takes over the terminal and would wipe anything
+  // printed before it, so its diagnostics wait until it is closed
+  let diagnostics = validation::validate(&located_specs);
+  if !shows_ncurses {
+    print_diagnostics(&diagnostics);
+  }
+  if cli_args.flag_check {
+    if !diagnostics.is_empty() {
+      process::exit(1);
+    }
+    return;
+  }
+
+  let spec_ok = validation::retain_valid(located_specs);
+
+  for format in cli_args.flag_emit.iter() {
+    match &format.to_lowercase()[..] {
+      "cog" => print!("{}", writer::to_source(&spec_ok)),
+      "dot" => print!("{}", dot::export::to_dot(&spec_ok)),
+      "mermaid" => print!("{}", mermaid::export::to_mermaid(&spec_ok)),
+      "json" => print!("{}", json::to_json(&spec_ok)),
+      _ => unreachable!()
+    }
+    return;
+  }
+
+  let settings = match load_settings(&cli_args) {
+    Ok(s) => s,
+    Err(e) => fail(e)
   };
+  let full_constraint = &settings.constraint;
+
+  let (blocks, connections) = split_specs(spec_ok);
 
-  let conn_constraint =
-    ConnectionConstraint{
-      min_length: 10,
-      max_length: 1000,
-      box_distance: 3};
-
-  let full_constraint =
-    LayoutConstraint {
-      connection: conn_constraint,
-      block: constraint,
-      max_width: 150,
-      max_height: 100
+  let layout_options =
+    LayoutOptions{
+      screen_width: settings.screen_width,
+      screen_height: settings.screen_height,
+      max_width: full_constraint.max_width};
+  let layout_manager =
+    match create_layout_manager(&settings.layout, &layout_options) {
+      Ok(manager) => manager,
+      Err(e) => fail(e)
     };
 
-  let split_spec: (Vec<_>, Vec<_>) = spec_ok.into_iter().partition(
+  let layout =
+    layout_manager.determine_block_vector_layout(
+      blocks.as_slice(), connections.as_slice(), &full_constraint.block);
+
+  let connections =
+    layout_manager.determine_connection_layout(
+      connections.as_slice(),
+      layout.as_slice(),
+      full_constraint);
+
+  match &cli_args.flag_output[..] {
+    "ncurses" => {
+      draw_ncurses(&layout, &connections, settings.charset);
+      print_diagnostics(&diagnostics);
+    }
+    "text" => render::text::print_diagram(&layout, &connections, settings.charset),
+    "ansi" => render::ansi::print_diagram(&layout, &connections, settings.charset),
+    "html" => render::html::print_diagram(&layout, &connections, settings.charset),
+    "svg" => render::svg::print_diagram(&layout, &connections),
+    "json" => render::json::print_diagram(&layout, &connections),
+    _ => unreachable!()
+  }
+}
+
+fn print_diagnostics(diagnostics: &[validation::Diagnostic]) {
+  for diagnostic in diagnostics.iter() {
+    let _ = writeln!(&mut io::stderr(), "{}", diagnostic);
+  }
+}
+
+/// Prints an error to stderr and ends the program with a failure status.
+fn fail<D: Display>(error: D) -> ! {
+  let _ = writeln!(&mut io::stderr(), "{}", error);
+  process::exit(1);
+}
+
+/// Separates blocks from connections, keeping the order of each.
+fn split_specs(specs: Vec<DataSpec>) -> (Vec<BlockSpec>, Vec<Connection>) {
+  let split_spec: (Vec<_>, Vec<_>) = specs.into_iter().partition(
     |ds| if let &DataSpec::BlockDataSpec(_) = ds {
       true
     } else {
@@ -80,7 +224,6 @@ fn main() {
     }
   );
 
-
   let blocks:Vec<BlockSpec> = split_spec.0.into_iter().filter_map(
     |ds| match ds {
       DataSpec::BlockDataSpec(block) => Some(block),
@@ -94,30 +237,188 @@ fn main() {
         _   => None
       }).collect();
 
-  let layout_manager = CrossingDownwardLayout{screen_width:50, screen_height: 50};
+  (blocks, connections)
+}
 
-  let layout = layout_manager.determine_block_vector_layout(blocks.as_slice(), &full_constraint.block);
+/// Shows the diagram in the ncurses viewer, reading it again and laying
+/// it out afresh whenever the file changes. Problems with the file are
+/// shown in a status line, leaving the last diagram that could be read
+/// on the screen, instead of ending the program.
+fn watch(cli_args: &Args, settings: &config::Settings) {
+  let path = &cli_args.arg_infile;
+  if path == "-" {
+    fail("Can't watch stdin, give the path of a file instead");
+  }
+  let format = input_format(path, &cli_args.flag_from);
+  let full_constraint = &settings.constraint;
 
-  let connections =
-    layout_manager.determine_connection_layout(
-      connections.as_slice(),
-      layout.as_slice(),
-      &full_constraint);
+  let layout_options =
+    LayoutOptions{
+      screen_width: settings.screen_width,
+      screen_height: settings.screen_height,
+      max_width: full_constraint.max_width};
+  let layout_manager =
+    match create_layout_manager(&settings.layout, &layout_options) {
+      Ok(manager) => manager,
+      Err(e) => fail(e)
+    };
+
+  let mut source = vec![];
+  let mut blocks = vec![];
+  let mut connections = vec![];
+  let mut status: Option<String>;
+  let mut viewport = Viewport::new(Size{width: 0, height: 0}, Size{width: 0, height: 0});
+
+  init_ncurses();
+  loop {
+    match read_source(path) {
+      Ok(new_source) => {
+        source = new_source;
+        match read_specs(&source, &format) {
+          Ok(specs) => {
+            status = validation::validate(&specs).first().map(|d| d.to_string());
+            let (new_blocks, new_connections) = split_specs(validation::retain_valid(specs));
+            blocks = new_blocks;
+            connections = new_connections;
+          }
+          Err(e) => status = Some(e)
+        }
+      }
+      Err(e) => {
+        source = vec![];
+        status = Some(format!("Could not read {}: {}", path, e.description()));
+      }
+    }
+
+    let changed = {
+      let layout =
+        layout_manager.determine_block_vector_layout(
+          blocks.as_slice(), connections.as_slice(), &full_constraint.block);
+      let connection_layout =
+        layout_manager.determine_connection_layout(
+          connections.as_slice(),
+          layout.as_slice(),
+          full_constraint);
+
+      // A file that can't be read counts as changed only the first time
+      let shown = &source;
+      view_until_changed(
+        &mut viewport, &layout, &connection_layout, settings.charset,
+        status.as_ref().map(|s| &s[..]),
+        || read_source(path).map(|s| s != *shown).unwrap_or(!shown.is_empty()))
+    };
+    if !changed {
+      break;
+    }
+  }
+  ncurses::endwin();
+}
+
+/// Prints the canonical form of a source file, or writes it back
+/// to the file. Source read from stdin is always printed.
+fn format_file(path: &str, write: bool) {
+  let source = match read_source(path) {
+    Ok(source) => source,
+    Err(e) => fail(format!("Could not read {}: {}", path, e.description()))
+  };
+
+  let formatted = match format::format_source(&source) {
+    Ok(f) => f,
+    Err(e) => fail(e)
+  };
 
+  if write && path != "-" {
+    if let Err(e) = File::create(path).and_then(|mut f| f.write_all(formatted.as_bytes())) {
+      fail(format!("Could not write {}: {}", path, e.description()));
+    }
+  } else {
+    print!("{}", formatted);
+  }
+}
+
+/// Reads the whole input file, or stdin when the path is -.
+fn read_source(path: &str) -> io::Result<Vec<u8>> {
+  if path == "-" {
+    ReaderDriver::new(io::stdin()).read_source()
+  } else {
+    FileDriver::new(path).and_then(|driver| driver.read_source())
+  }
+}
+
+/// The format named by --from, or else the one the file extension
+/// suggests.
+fn input_format(path: &str, from: &Option<String>) -> String {
+  match *from {
+    Some(ref format) => format.to_lowercase(),
+    None =>
+      match Path::new(path).extension().and_then(|e| e.to_str()) {
+        Some("dot") | Some("gv") => "dot".to_owned(),
+        Some("mmd") | Some("mermaid") => "mermaid".to_owned(),
+        Some("json") => "json".to_owned(),
+        _ => "cog".to_owned()
+      }
+  }
+}
+
+fn read_specs(source: &[u8], format: &str) -> Result<Vec<LocatedSpec>, String> {
+  match format {
+    "cog" => parse_located_specification(source).map_err(|e| e.to_string()),
+    "dot" => dot::import::parse_dot(source).map_err(|e| e.to_string()),
+    "mermaid" => mermaid::import::parse_mermaid(source).map_err(|e| e.to_string()),
+    "json" => json::parse_json(source).map_err(|e| e.to_string()),
+    other => Err(format!("Unknown input format {}", other))
+  }
+}
+
+/// Builds the layout settings from the defaults, the configuration file
+/// and then the command line options, with later ones taking precedence.
+fn load_settings(cli_args: &Args) -> Result<config::Settings, config::ConfigError> {
+  let mut settings = config::Settings::new();
+
+  let config_path =
+    match cli_args.flag_config {
+      Some(ref path) => Some(PathBuf::from(path)),
+      None => config::find_project_config(Path::new(&cli_args.arg_infile))
+    };
+  for path in config_path {
+    try!(settings.apply_config_file(&path));
+  }
+
+  let overrides = [
+    ("min-block-width", &cli_args.flag_min_block_width),
+    ("max-height-per-width", &cli_args.flag_max_height_per_width),
+    ("max-width-per-height", &cli_args.flag_max_width_per_height),
+    ("block-spacing", &cli_args.flag_block_spacing),
+    ("min-connection-length", &cli_args.flag_min_connection_length),
+    ("max-connection-length", &cli_args.flag_max_connection_length),
+    ("box-distance", &cli_args.flag_box_distance),
+    ("max-width", &cli_args.flag_max_width),
+    ("max-height", &cli_args.flag_max_height),
+    ("screen-width", &cli_args.flag_screen_width),
+    ("screen-height", &cli_args.flag_screen_height),
+    ("layout", &cli_args.flag_layout),
+    ("charset", &cli_args.flag_charset)];
+  for &(key, value) in overrides.iter() {
+    for v in value.iter() {
+      try!(settings.set(key, v));
+    }
+  }
+
+  Ok(settings)
+}
+
+fn init_ncurses() {
   ncurses::setlocale(ncurses::LcCategory::all, "");
   ncurses::initscr();
   ncurses::start_color();
   ncurses::use_default_colors();
   ncurses::cbreak();
 
-  data::Coloring::init_default_color_pairs();
+  render::curses::init_default_color_pairs();
+}
 
-  for block in layout {
-    render::block::draw_block_display(Position{x:0, y:0}, block.1);
-  }
-  for connection in connections.iter() {
-    render::connection::draw_connection(Position{x:0, y:0}, connection);
-  }
-  ncurses::getch();
+fn draw_ncurses(blocks: &[(&BlockSpec, BlockDisplay)], connections: &[ConnectionDisplay], charset: CharSet) {
+  init_ncurses();
+  render::viewer::run_viewer(blocks, connections, charset);
   ncurses::endwin();
 }
@@ -0,0 +1,143 @@
+use data::*;
+use writer::color_name;
+
+/// Writes specifications as a Mermaid flowchart.
+///
+/// Blocks become nodes holding their text. Singular connections are
+/// written as `-->`, dual ones as `<-->` and generic ones as `---`. Block
+/// colours are given by a `classDef` for each colour and connection
+/// colours by `linkStyle`.
+pub fn to_mermaid(specs: &[DataSpec]) -> String {
+  let mut out = String::from("flowchart TD\n");
+  let mut classes: Vec<(Coloring, Vec<String>)> = vec![];
+  let mut link_styles = vec![];
+
+  for spec in specs.iter() {
+    if let DataSpec::BlockDataSpec(BlockSpec::Boxed(ref name, color, ref text)) = *spec {
+      out.push_str(&format!("  {}[\"{}\"]\n", node_id(name), escape(text)));
+      if color != Coloring::Default {
+        match classes.iter().position(|c| c.0 == color) {
+          Some(i) => classes[i].1.push(node_id(name)),
+          None => classes.push((color, vec![node_id(name)]))
+        }
+      }
+    }
+  }
+
+  let connections = specs.iter().filter_map(|spec| match *spec {
+    DataSpec::ConnectionDataSpec(ref conn) => Some(conn),
+    _ => None
+  });
+  for (i, conn) in connections.enumerate() {
+    let link =
+      match conn.ty {
+        ConnectionType::Singular => "-->",
+        ConnectionType::Dual => "<-->",
+        ConnectionType::Generic => "---"
+      };
+    let label =
+      conn.label.as_ref().map(|l| format!("|\"{}\"|", escape(l))).unwrap_or(String::new());
+    out.push_str(&format!("  {} {}{} {}\n", node_id(&conn.start), link, label, node_id(&conn.end)));
+    if conn.color != Coloring::Default {
+      link_styles.push((i, conn.color));
+    }
+  }
+
+  for &(color, ref names) in classes.iter() {
+    let color = color_name(color);
+    out.push_str(&format!("  classDef {} stroke:{},color:{}\n", color, color, color));
+    out.push_str(&format!("  class {} {}\n", names.join(","), color));
+  }
+  for &(i, color) in link_styles.iter() {
+    out.push_str(&format!("  linkStyle {} stroke:{}\n", i, color_name(color)));
+  }
+
+  out
+}
+
+/// Words that start a statement rather than a node when they begin a line
+const RESERVED: [&'static str; 8] =
+  ["end", "class", "classDef", "style", "linkStyle", "subgraph", "direction", "click"];
+
+/// A block name as a Mermaid node id. Blocks named after a reserved word
+/// are written with `_` after it, which is dropped when reading it back.
+fn node_id(name: &str) -> String {
+  if RESERVED.contains(&name) {format!("{}_", name)} else {name.to_owned()}
+}
+
+/// Text for a quoted node or link label, with line breaks as `<br/>` and
+/// characters Mermaid would read as markup written as entity codes.
+fn escape(text: &str) -> String {
+  let mut escaped = String::new();
+  for c in text.chars() {
+    match c {
+      '#' => escaped.push_str("#35;"),
+      '"' => escaped.push_str("#quot;"),
+      '<' => escaped.push_str("#lt;"),
+      '>' => escaped.push_str("#gt;"),
+      '\n' => escaped.push_str("<br/>"),
+      c => escaped.push(c)
+    }
+  }
+  escaped
+}
+
+#[cfg(test)]
+mod test {
+  use super::{to_mermaid, RESERVED};
+  use data::*;
+  use data::fixtures::*;
+  use mermaid::import::parse_mermaid;
+
+  fn specs() -> Vec<DataSpec> {
+    vec![
+      block("lb", Coloring::Blue, "Load\nbalancer"),
+      block("db", Coloring::Blue, "say \"hi\" #1"),
+      block("cache", Coloring::Default, "<cache>"),
+      conn(ConnectionType::Singular, "lb", "db", Coloring::Default, Some("sql")),
+      conn(ConnectionType::Dual, "db", "cache", Coloring::Red, None),
+      conn(ConnectionType::Generic, "cache", "lb", Coloring::Default, None)]
+  }
+
+  #[test]
+  fn writes_flowchart() {
+    assert_eq!(to_mermaid(&specs()),
+      "flowchart TD\n\
+       \x20 lb[\"Load<br/>balancer\"]\n\
+       \x20 db[\"say #quot;hi#quot; #35;1\"]\n\
+       \x20 cache[\"#lt;cache#gt;\"]\n\
+       \x20 lb -->|\"sql\"| db\n\
+       \x20 db <--> cache\n\
+       \x20 cache --- lb\n\
+       \x20 classDef blue stroke:blue,color:blue\n\
+       \x20 class lb,db blue\n\
+       \x20 linkStyle 1 stroke:red\n");
+  }
+
+  #[test]
+  fn renames_reserved_words() {
+    let mut specs = vec![block("start", Coloring::Default, "Start")];
+    for word in RESERVED.iter() {
+      specs.push(block(word, Coloring::Red, "x"));
+    }
+    for word in RESERVED.iter() {
+      specs.push(conn(ConnectionType::Singular, word, "start", Coloring::Default, None));
+    }
+
+    let mermaid = to_mermaid(&specs);
+    for word in RESERVED.iter() {
+      assert!(mermaid.contains(&format!("  {}_ --> start\n", word)));
+    }
+    let read:Vec<DataSpec> =
+      parse_mermaid(mermaid.as_bytes()).unwrap().into_iter().map(|(_, spec)| spec).collect();
+    assert_eq!(read, specs);
+  }
+
+  #[test]
+  fn reads_back_the_same() {
+    let specs = specs();
+    let read:Vec<DataSpec> =
+      parse_mermaid(to_mermaid(&specs).as_bytes()).unwrap().into_iter().map(|(_, spec)| spec).collect();
+    assert_eq!(read, specs);
+  }
+}
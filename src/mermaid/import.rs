@@ -0,0 +1,617 @@
+use data::*;
+use dot::import::{color_by_name, unique_name};
+use parser::driver::LocatedSpec;
+use parser::location::SourceLocation;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+use std::str;
+
+/// A problem reading a Mermaid flowchart, and where it is.
+#[derive(Debug, PartialEq, Eq)]
+pub struct MermaidError {
+  pub message: String,
+  pub location: SourceLocation
+}
+
+impl Display for MermaidError {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    write!(f, "{} at {}\n{}", self.message, self.location, self.location.snippet())
+  }
+}
+
+impl Error for MermaidError {
+  fn description(&self) -> &str {
+    &self.message
+  }
+}
+
+/// Reads a Mermaid flowchart.
+///
+/// Each node becomes a box holding its text, whatever its shape, and each
+/// link becomes a connection: `-->` is singular, `<-->` dual and `---`
+/// generic, in any of the dotted or thick styles. Link text written as
+/// `-->|text|` or `-- text -->` becomes the connection label.
+///
+/// Colours are taken from the `stroke`, `color` or `fill` of `classDef`
+/// classes given to nodes with `class` or `:::`, from `style` and from
+/// `linkStyle`. Subgraphs are flattened and interaction is ignored.
+pub fn parse_mermaid(source: &[u8]) -> Result<Vec<LocatedSpec>, MermaidError> {
+  let text =
+    try!(str::from_utf8(source).map_err(
+      |_| error(source, 0, "Mermaid source is not valid UTF-8")));
+
+  let mut chart = Chart::new();
+  let mut seen_header = false;
+  for (statement, offset) in statements(text) {
+    let mut parser = StatementParser{source: source, text: statement, offset: offset, pos: 0};
+    if seen_header {
+      try!(parser.parse_statement(&mut chart));
+    } else {
+      try!(parser.parse_header());
+      seen_header = true;
+    }
+  }
+  if !seen_header {
+    return Err(error(source, source.len(), "expected flowchart or graph"));
+  }
+
+  Ok(chart.to_specs(source))
+}
+
+fn error(source: &[u8], offset: usize, message: &str) -> MermaidError {
+  MermaidError{message: message.to_owned(), location: SourceLocation::from_offset(source, offset)}
+}
+
+/// Splits the source into statements, which end at a line break or at a
+/// `;` outside node text, along with the offset each one starts at.
+/// `%%` comment lines are left out.
+fn statements(text: &str) -> Vec<(&str, usize)> {
+  let mut found = vec![];
+  let mut line_start = 0;
+
+  for line in text.split('\n') {
+    if !line.trim().starts_with("%%") {
+      let mut start = 0;
+      let mut depth = 0;
+      let mut quoted = false;
+      for (i, c) in line.char_indices() {
+        match c {
+          '"' => quoted = !quoted,
+          '[' | '(' | '{' if !quoted => depth += 1,
+          ']' | ')' | '}' if !quoted => depth -= 1,
+          ';' if !quoted && depth <= 0 => {
+            found.push((&line[start..i], line_start + start));
+            start = i + 1;
+          }
+          _ => ()
+        }
+      }
+      found.push((&line[start..], line_start + start));
+    }
+    line_start += line.len() + 1;
+  }
+
+  found.into_iter()
+    .filter(|&(s, _)| !s.trim().is_empty())
+    .map(|(s, offset)| (s.trim(), offset + s.len() - s.trim_left().len()))
+    .collect()
+}
+
+struct Node {
+  id: String,
+  text: Option<String>,
+  offset: usize
+}
+
+struct Link {
+  from: String,
+  to: String,
+  /// Arrowheads at the start and end
+  heads: (bool, bool),
+  label: Option<String>,
+  offset: usize
+}
+
+struct Chart {
+  nodes: Vec<Node>,
+  links: Vec<Link>,
+  class_colors: HashMap<String, Coloring>,
+  node_classes: HashMap<String, Vec<String>>,
+  node_colors: HashMap<String, Coloring>,
+  link_colors: HashMap<usize, Coloring>,
+  default_link_color: Coloring
+}
+
+impl Chart {
+  fn new() -> Chart {
+    Chart{
+      nodes: vec![],
+      links: vec![],
+      class_colors: HashMap::new(),
+      node_classes: HashMap::new(),
+      node_colors: HashMap::new(),
+      link_colors: HashMap::new(),
+      default_link_color: Coloring::Default}
+  }
+
+  /// Adds a node the first time it is seen. Text given later replaces
+  /// text given earlier.
+  fn add_node(&mut self, id: &str, text: Option<String>, offset: usize) {
+    match self.nodes.iter().position(|n| n.id == id) {
+      Some(existing) => if text.is_some() {
+        self.nodes[existing].text = text;
+      },
+      None => self.nodes.push(Node{id: id.to_owned(), text: text, offset: offset})
+    }
+  }
+
+  fn add_class(&mut self, id: &str, class: &str) {
+    self.node_classes.entry(id.to_owned()).or_insert(vec![]).push(class.to_owned());
+  }
+
+  fn node_color(&self, id: &str) -> Coloring {
+    if let Some(color) = self.node_colors.get(id) {
+      return *color;
+    }
+    let classes = self.node_classes.get(id).map(|c| &c[..]).unwrap_or(&[]);
+    classes.iter().rev()
+      .chain(["default".to_owned()].iter())
+      .filter_map(|class| self.class_colors.get(class))
+      .next()
+      .cloned()
+      .unwrap_or(Coloring::Default)
+  }
+
+  fn to_specs(&self, source: &[u8]) -> Vec<LocatedSpec> {
+    let mut names: HashMap<&str, String> = HashMap::new();
+    let mut specs = vec![];
+
+    for node in self.nodes.iter() {
+      let name = unique_name(&node.id, names.values().map(|n| &n[..]).collect());
+      names.insert(&node.id, name.clone());
+
+      let text =
+        match node.text {
+          Some(ref text) if !text.trim().is_empty() => text.clone(),
+          _ => node.id.clone()
+        };
+      specs.push((
+        SourceLocation::from_offset(source, node.offset),
+        DataSpec::BlockDataSpec(BlockSpec::Boxed(name, self.node_color(&node.id), text))));
+    }
+
+    for (i, link) in self.links.iter().enumerate() {
+      let (ty, from, to) =
+        match link.heads {
+          (true, true) => (ConnectionType::Dual, &link.from, &link.to),
+          (false, true) => (ConnectionType::Singular, &link.from, &link.to),
+          (true, false) => (ConnectionType::Singular, &link.to, &link.from),
+          (false, false) => (ConnectionType::Generic, &link.from, &link.to)
+        };
+      specs.push((
+        SourceLocation::from_offset(source, link.offset),
+        DataSpec::ConnectionDataSpec(Connection{
+          ty: ty,
+          start: names[&from[..]].clone(),
+          end: names[&to[..]].clone(),
+          color: self.link_colors.get(&i).cloned().unwrap_or(self.default_link_color),
+          label: link.label.clone()})));
+    }
+
+    specs
+  }
+}
+
+struct StatementParser<'a> {
+  source: &'a [u8],
+  text: &'a str,
+  /// Offset of the statement in the source
+  offset: usize,
+  pos: usize
+}
+
+impl<'a> StatementParser<'a> {
+  fn parse_header(&mut self) -> Result<(), MermaidError> {
+    match &self.word().to_lowercase()[..] {
+      "flowchart" | "graph" => Ok(()),
+      _ => Err(self.error_at(0, "expected flowchart or graph"))
+    }
+  }
+
+  fn parse_statement(&mut self, chart: &mut Chart) -> Result<(), MermaidError> {
+    let keyword = self.word();
+    if !self.rest().is_empty() && !self.rest().starts_with(char::is_whitespace) {
+      self.pos = 0;
+      return self.parse_chain(chart);
+    }
+    self.skip_space();
+
+    match keyword {
+      "classDef" => {
+        let names = self.token();
+        for color in style_color(self.rest()) {
+          for name in names.split(',') {
+            chart.class_colors.insert(name.to_owned(), color);
+          }
+        }
+      }
+      "class" => {
+        let ids = self.token();
+        self.skip_space();
+        let class = self.token();
+        for id in ids.split(',') {
+          chart.add_class(id, class);
+        }
+      }
+      "style" => {
+        let id = self.token();
+        for color in style_color(self.rest()) {
+          chart.node_colors.insert(id.to_owned(), color);
+        }
+      }
+      "linkStyle" => {
+        let indices = self.token();
+        for color in style_color(self.rest()) {
+          for index in indices.split(',') {
+            match index.parse::<usize>() {
+              Ok(i) => {chart.link_colors.insert(i, color);}
+              Err(_) => if index == "default" {
+                chart.default_link_color = color;
+              }
+            }
+          }
+        }
+      }
+      "subgraph" | "end" | "direction" | "click" => (),
+      _ => {
+        self.pos = 0;
+        return self.parse_chain(chart);
+      }
+    }
+    Ok(())
+  }
+
+  /// Parses nodes joined by links, such as `a & b --> c[Text] --- d`.
+  fn parse_chain(&mut self, chart: &mut Chart) -> Result<(), MermaidError> {
+    let mut previous = try!(self.parse_group(chart));
+    loop {
+      self.skip_space();
+      if self.rest().is_empty() {
+        return Ok(());
+      }
+
+      let offset = self.offset + self.pos;
+      let (heads, label) = try!(self.parse_link());
+      let next = try!(self.parse_group(chart));
+      for from in previous.iter() {
+        for to in next.iter() {
+          chart.links.push(Link{
+            from: from.clone(),
+            to: to.clone(),
+            heads: heads,
+            label: label.clone(),
+            offset: offset});
+        }
+      }
+      previous = next;
+    }
+  }
+
+  /// Parses nodes separated by `&`, returning their ids.
+  fn parse_group(&mut self, chart: &mut Chart) -> Result<Vec<String>, MermaidError> {
+    let mut ids = vec![];
+    loop {
+      self.skip_space();
+      ids.push(try!(self.parse_node(chart)));
+      self.skip_space();
+      if !self.rest().starts_with('&') {
+        return Ok(ids);
+      }
+      self.pos += 1;
+    }
+  }
+
+  fn parse_node(&mut self, chart: &mut Chart) -> Result<String, MermaidError> {
+    let offset = self.offset + self.pos;
+    let id = self.word();
+    if id.is_empty() {
+      return Err(self.error("expected a node name"));
+    }
+
+    let text = try!(self.parse_shape());
+    chart.add_node(id, text, offset);
+
+    if self.rest().starts_with(":::") {
+      self.pos += 3;
+      let class = self.word();
+      chart.add_class(id, class);
+    }
+    Ok(id.to_owned())
+  }
+
+  /// Parses the text of a node in any of the shapes, such as `[text]`,
+  /// `([text])` or `{{text}}`, if there is one.
+  fn parse_shape(&mut self) -> Result<Option<String>, MermaidError> {
+    let mut open:String = self.rest().chars().take_while(|c| "[({".contains(*c)).take(3).collect();
+    if open.is_empty() && self.rest().starts_with('>') {
+      open.push('>');
+    }
+    if open.is_empty() {
+      return Ok(None);
+    }
+    let close:String = open.chars().rev().map(closing_bracket).collect();
+    self.pos += open.len();
+
+    let raw =
+      if self.rest().starts_with('"') {
+        let end = try!(self.rest()[1..].find('"').ok_or(self.error("unterminated string")));
+        let text = &self.rest()[1..end + 1];
+        self.pos += end + 2;
+        if !self.rest().starts_with(&close[..]) {
+          return Err(self.error(&format!("expected {}", close)));
+        }
+        text
+      } else {
+        let end = try!(self.rest().find(&close[..]).ok_or(self.error("unterminated node text")));
+        let text = self.rest()[..end].trim();
+        self.pos += end;
+        if open == "[" {text.trim_matches(|c| c == '/' || c == '\\')} else {text}
+      };
+    self.pos += close.len();
+
+    Ok(Some(decode_text(raw).trim().to_owned()))
+  }
+
+  /// Parses a link, such as `-->`, `<==>`, `-.-`, `-->|text|` or
+  /// `-- text -->`, returning its arrowheads and label.
+  fn parse_link(&mut self) -> Result<((bool, bool), Option<String>), MermaidError> {
+    let start_head = self.rest().starts_with('<');
+    if start_head {
+      self.pos += 1;
+    }
+    let line = self.link_line();
+    if line.len() < 2 {
+      return Err(self.error("expected a link such as -->"));
+    }
+    let mut end_head = self.link_end();
+    let mut label = None;
+
+    if !start_head && !end_head && (line == "--" || line == "==" || line == "-.") &&
+        self.rest().starts_with(char::is_whitespace) {
+      // Text inside the link, as in a -- text --> b
+      let rest = self.rest();
+      let end = try!(["--", "==", ".-"].iter().filter_map(|op| rest.find(op)).min()
+                       .ok_or(self.error("unterminated link text")));
+      label = Some(rest[..end].to_owned());
+      self.pos += end;
+      self.link_line();
+      end_head = self.link_end();
+    }
+
+    self.skip_space();
+    if self.rest().starts_with('|') {
+      let end = try!(self.rest()[1..].find('|').ok_or(self.error("unterminated link text")));
+      label = Some(self.rest()[1..end + 1].trim().trim_matches('"').to_owned());
+      self.pos += end + 2;
+    }
+
+    let label =
+      label.map(|l| decode_text(&l).replace('\n', " ").replace('"', "'").trim().to_owned())
+        .and_then(|l| if l.is_empty() {None} else {Some(l)});
+    Ok(((start_head, end_head), label))
+  }
+
+  /// The dashes, equals signs and dots making up the line of a link
+  fn link_line(&mut self) -> &'a str {
+    let len = self.rest().find(|c: char| !"-=.".contains(c)).unwrap_or(self.rest().len());
+    let line = &self.rest()[..len];
+    self.pos += len;
+    line
+  }
+
+  /// Whether a link ends with an arrowhead. Circle and cross ends count
+  /// as arrowheads.
+  fn link_end(&mut self) -> bool {
+    let mut chars = self.rest().chars();
+    let head =
+      match (chars.next(), chars.next()) {
+        (Some('>'), _) => true,
+        (Some('o'), next) | (Some('x'), next) => next.map(|c| !is_word_char(c)).unwrap_or(true),
+        _ => false
+      };
+    if head {
+      self.pos += 1;
+    }
+    head
+  }
+
+  fn rest(&self) -> &'a str {
+    &self.text[self.pos..]
+  }
+
+  /// A node id, class name or keyword
+  fn word(&mut self) -> &'a str {
+    let len = self.rest().find(|c: char| !is_word_char(c)).unwrap_or(self.rest().len());
+    let word = &self.rest()[..len];
+    self.pos += len;
+    word
+  }
+
+  /// Everything up to the next whitespace
+  fn token(&mut self) -> &'a str {
+    let len = self.rest().find(char::is_whitespace).unwrap_or(self.rest().len());
+    let token = &self.rest()[..len];
+    self.pos += len;
+    token
+  }
+
+  fn skip_space(&mut self) {
+    self.pos = self.text.len() - self.rest().trim_left().len();
+  }
+
+  fn error(&self, message: &str) -> MermaidError {
+    self.error_at(self.pos, message)
+  }
+
+  fn error_at(&self, pos: usize, message: &str) -> MermaidError {
+    error(self.source, self.offset + pos, message)
+  }
+}
+
+fn is_word_char(c: char) -> bool {
+  c.is_alphanumeric() || c == '_'
+}
+
+fn closing_bracket(open: char) -> char {
+  match open {
+    '[' | '>' => ']',
+    '(' => ')',
+    _ => '}'
+  }
+}
+
+/// Text with `<br>` tags as line breaks and `#name;` or `#number;`
+/// entity codes replaced by the characters they stand for.
+fn decode_text(text: &str) -> String {
+  let text = text.replace("<br />", "\n").replace("<br/>", "\n").replace("<br>", "\n");
+  let mut decoded = String::new();
+  let mut rest = &text[..];
+
+  while let Some(hash) = rest.find('#') {
+    decoded.push_str(&rest[..hash]);
+    rest = &rest[hash..];
+    let entity =
+      rest[1..].find(';').and_then(|end| {
+        let name = &rest[1..end + 1];
+        let c =
+          match name {
+            "quot" => Some('"'),
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            _ => name.parse::<u32>().ok().and_then(::std::char::from_u32)
+          };
+        c.map(|c| (c, end + 2))
+      });
+    match entity {
+      Some((c, len)) => {
+        decoded.push(c);
+        rest = &rest[len..];
+      }
+      None => {
+        decoded.push('#');
+        rest = &rest[1..];
+      }
+    }
+  }
+  decoded.push_str(rest);
+  decoded
+}
+
+/// The colour given by CSS style properties such as
+/// `fill:#f9f,stroke:red`, taken from the stroke, text colour or fill in
+/// that order of preference.
+fn style_color(style: &str) -> Option<Coloring> {
+  let properties:Vec<(&str, &str)> =
+    style.split(|c| c == ',' || c == ';')
+      .filter_map(|property| {
+        let mut parts = property.splitn(2, ':');
+        match (parts.next(), parts.next()) {
+          (Some(key), Some(value)) => Some((key.trim(), value.trim())),
+          _ => None
+        }
+      })
+      .collect();
+
+  ["stroke", "color", "fill"].iter()
+    .filter_map(|key| properties.iter().find(|p| p.0 == *key))
+    .map(|p| css_color(p.1))
+    .next()
+}
+
+/// The cog colour closest to a CSS colour name or `#rgb` or `#rrggbb`
+/// hex colour.
+fn css_color(color: &str) -> Coloring {
+  if !color.starts_with('#') {
+    return color_by_name(color);
+  }
+
+  let hex = &color[1..];
+  if !hex.chars().all(|c| c.is_digit(16)) {
+    return Coloring::Default;
+  }
+  let channels:Vec<u8> =
+    match hex.len() {
+      3 | 4 => hex.chars().take(3).filter_map(|c| c.to_digit(16)).map(|d| d as u8 * 17).collect(),
+      6 | 8 =>
+        (0..3).filter_map(|i| u8::from_str_radix(&hex[i * 2..i * 2 + 2], 16).ok()).collect(),
+      _ => vec![]
+    };
+  if channels.len() != 3 {
+    return Coloring::Default;
+  }
+
+  match (channels[0] >= 128, channels[1] >= 128, channels[2] >= 128) {
+    (false, false, false) => Coloring::Black,
+    (true, false, false) => Coloring::Red,
+    (false, true, false) => Coloring::Green,
+    (true, true, false) => Coloring::Yellow,
+    (false, false, true) => Coloring::Blue,
+    (true, false, true) => Coloring::Magenta,
+    (false, true, true) => Coloring::Cyan,
+    (true, true, true) => Coloring::White
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::parse_mermaid;
+  use data::*;
+  use data::fixtures::*;
+
+  fn specs(source: &str) -> Vec<DataSpec> {
+    parse_mermaid(source.as_bytes()).unwrap().into_iter().map(|(_, spec)| spec).collect()
+  }
+
+  #[test]
+  fn reads_flowchart() {
+    let source = "
+      %% The web tier
+      flowchart LR
+        lb([\"Load<br/>balancer\"]):::edge --> app1[App #35;1] & app2{{App 2}}
+        app1 -->|sql| db[(Database)]; app2 -- reads --> db
+        db <-.-> cache>Cache]
+        cache --- lb
+        classDef edge stroke:#00f,fill:#eee
+        style db color:red
+        linkStyle 4 stroke:green
+    ";
+    assert_eq!(specs(source), vec![
+      block("lb", Coloring::Blue, "Load\nbalancer"),
+      block("app1", Coloring::Default, "App #1"),
+      block("app2", Coloring::Default, "App 2"),
+      block("db", Coloring::Red, "Database"),
+      block("cache", Coloring::Default, "Cache"),
+      conn(ConnectionType::Singular, "lb", "app1", Coloring::Default, None),
+      conn(ConnectionType::Singular, "lb", "app2", Coloring::Default, None),
+      conn(ConnectionType::Singular, "app1", "db", Coloring::Default, Some("sql")),
+      conn(ConnectionType::Singular, "app2", "db", Coloring::Default, Some("reads")),
+      conn(ConnectionType::Dual, "db", "cache", Coloring::Green, None),
+      conn(ConnectionType::Generic, "cache", "lb", Coloring::Default, None)]);
+  }
+
+  #[test]
+  fn needs_a_header() {
+    let error = parse_mermaid(b"sequenceDiagram\n  a->>b: hi").unwrap_err();
+    assert_eq!(error.message, "expected flowchart or graph");
+  }
+
+  #[test]
+  fn errors_have_locations() {
+    let error = parse_mermaid(b"graph TD\n  a --> b[unclosed").unwrap_err();
+    assert_eq!(error.message, "unterminated node text");
+    assert_eq!((error.location.line, error.location.column), (2, 11));
+  }
+}
@@ -0,0 +1,4 @@
+//! Conversion between cog diagrams and Mermaid flowcharts.
+
+pub mod import;
+pub mod export;
@@ -5,34 +5,71 @@ use std::fs::File;
 use std::error::Error;
 use std::fmt;
 use std::fmt::{Display, Formatter};
+use std::ops::Range;
+use std::str;
 use nom::IResult;
 use nom::Err as NomErr;
 
 use super::nodes;
+use super::location::SourceLocation;
 
 #[derive(Debug)]
 pub enum ParserError{
-  NomIncomplete,
-  NomErr(::nom::ErrorKind, String),
-  NotAllParsed,
+  NomIncomplete(SourceLocation),
+  NomErr(::nom::ErrorKind, SourceLocation, String),
+  NotAllParsed(SourceLocation),
+  /// The source isn't UTF-8. Holds the location of the first bad byte.
+  InvalidUtf8(SourceLocation),
   IoErr(io::Error)
 }
 
-fn construct_nom_err(ek: ::nom::ErrorKind) -> ParserError {
-  let msg = format!("nom parsing failed due to error {:?}", ek);
-  ParserError::NomErr(ek, msg)
+impl ParserError {
+  /// Builds an error from a nom error produced while parsing `source`.
+  /// The error is placed at the deepest position nom reported, or the
+  /// start of the source if it reported none.
+  pub fn from_nom_err<'a>(source: &'a [u8], e: NomErr<&'a [u8]>) -> ParserError {
+    let location =
+      match error_position(&e) {
+        Some(remaining) => SourceLocation::from_remaining(source, remaining),
+        None => SourceLocation::from_offset(source, 0)
+      };
+    let ek = error_kind(&e);
+    let msg = format!("nom parsing failed due to error {:?}", ek);
+    ParserError::NomErr(ek, location, msg)
+  }
+
+  /// The place in the source the error refers to, if it has one.
+  pub fn location(&self) -> Option<&SourceLocation> {
+    use self::ParserError::*;
+    match *self {
+      NomIncomplete(ref loc) => Some(loc),
+      NomErr(_, ref loc, _) => Some(loc),
+      NotAllParsed(ref loc) => Some(loc),
+      InvalidUtf8(ref loc) => Some(loc),
+      IoErr(_) => None
+    }
+  }
 }
 
-impl<'a> From<NomErr<&'a [u8]>> for ParserError {
-  fn from(e: NomErr<&'a [u8]>) -> ParserError {
-    use nom::Err::*;
+fn error_kind<'a>(e: &NomErr<&'a [u8]>) -> ::nom::ErrorKind {
+  use nom::Err::*;
 
-    match e {
-      Code(uval) => construct_nom_err(uval),
-      Node(uval, _) => construct_nom_err(uval),
-      Position(uval, _) => construct_nom_err(uval),
-      NodePosition(uval, _, _) => construct_nom_err(uval),
-    }
+  match *e {
+    Code(ref kind) => kind.clone(),
+    Node(ref kind, _) => kind.clone(),
+    Position(ref kind, _) => kind.clone(),
+    NodePosition(ref kind, _, _) => kind.clone(),
+  }
+}
+
+fn error_position<'a>(e: &NomErr<&'a [u8]>) -> Option<&'a [u8]> {
+  use nom::Err::*;
+
+  match *e {
+    Code(_) => None,
+    Node(_, ref next) => error_position(next),
+    Position(_, pos) => Some(pos),
+    NodePosition(_, pos, ref next) => error_position(next).or(Some(pos)),
   }
 }
 
@@ -44,7 +81,10 @@ impl From<io::Error> for ParserError {
 
 impl Display for ParserError {
   fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
-    self.description().fmt(f)
+    match self.location() {
+      Some(loc) => write!(f, "{} at {}\n{}", self.description(), loc, loc.snippet()),
+      None => self.description().fmt(f)
+    }
   }
 }
 
@@ -52,9 +92,10 @@ impl Error for ParserError {
   fn description(&self) -> &str {
     use self::ParserError::*;
     match *self  {
-      NomIncomplete => "nom parsing failed as it was incomplete",
-      NomErr(_, ref string) => string,
-      NotAllParsed => "parsing failed as not all input was parsed",
+      NomIncomplete(_) => "parsing failed due to an unexpected end of input",
+      NomErr(_, _, ref string) => string,
+      NotAllParsed(_) => "parsing failed due to an unrecognized statement",
+      InvalidUtf8(_) => "parsing failed due to text that is not valid UTF-8",
       IoErr(_) => "parsing failed due to an I/O error"
     }
   }
@@ -68,8 +109,26 @@ impl Error for ParserError {
   }
 }
 
-pub trait ParserDriver {
-  fn read_to_specification(self) -> Result<Vec<DataSpec>, ParserError>;
+/// A parsed statement and where it starts in the source
+pub type LocatedSpec = (SourceLocation, DataSpec);
+
+/// A parsed statement and the bytes of the source it takes up, which
+/// include any comments after it on its last line
+pub type SpannedSpec = (Range<usize>, DataSpec);
+
+pub trait ParserDriver: Sized {
+  /// Reads the whole source without parsing it
+  fn read_source(self) -> io::Result<Vec<u8>>;
+
+  fn read_to_located_specification(self) -> Result<Vec<LocatedSpec>, ParserError> {
+    let source = try!(self.read_source());
+    parse_located_specification(&source)
+  }
+
+  fn read_to_specification(self) -> Result<Vec<DataSpec>, ParserError> {
+    self.read_to_located_specification()
+      .map(|specs| specs.into_iter().map(|(_, spec)| spec).collect())
+  }
 }
 
 pub struct FileDriver {
@@ -86,18 +145,151 @@ impl FileDriver{
 }
 
 impl ParserDriver for FileDriver {
-  fn read_to_specification(mut self) -> Result<Vec<DataSpec>, ParserError> {
-    use self::ParserError::*;
-
+  fn read_source(mut self) -> io::Result<Vec<u8>> {
     let mut buf = vec!();
     try!(self.file.read_to_end(&mut buf));
+    Ok(buf)
+  }
+}
+
+/// Reads source from anything that can be read from, such as stdin.
+pub struct ReaderDriver<R: Read> {
+  reader: R
+}
+
+impl<R: Read> ReaderDriver<R> {
+  pub fn new(reader: R) -> ReaderDriver<R> {
+    ReaderDriver{reader: reader}
+  }
+}
+
+impl<R: Read> ParserDriver for ReaderDriver<R> {
+  fn read_source(mut self) -> io::Result<Vec<u8>> {
+    let mut buf = vec!();
+    try!(self.reader.read_to_end(&mut buf));
+    Ok(buf)
+  }
+}
+
+/// Parses source that is already in memory.
+pub struct StrDriver<'a> {
+  source: &'a str
+}
 
-    let nom_result = nodes::full_graph_spec(&buf);
-    match nom_result {
-      IResult::Done(rem, _) if rem.len() > 0 => {println!("{:?}", String::from_utf8(rem.to_owned())); Err(NotAllParsed)}
-      IResult::Done(_, out) => Ok(out),
-      IResult::Incomplete(_) => Err(NomIncomplete),
-      IResult::Error(err) => Err(ParserError::from(err)),
+impl<'a> StrDriver<'a> {
+  pub fn new(source: &'a str) -> StrDriver<'a> {
+    StrDriver{source: source}
+  }
+}
+
+impl<'a> ParserDriver for StrDriver<'a> {
+  fn read_source(self) -> io::Result<Vec<u8>> {
+    Ok(self.source.as_bytes().to_owned())
+  }
+}
+
+/// Parses a complete .cog source into its specification.
+pub fn parse_specification(source: &[u8]) -> Result<Vec<DataSpec>, ParserError> {
+  parse_located_specification(source)
+    .map(|specs| specs.into_iter().map(|(_, spec)| spec).collect())
+}
+
+/// Parses a complete .cog source, keeping the location of each statement.
+pub fn parse_located_specification(source: &[u8]) -> Result<Vec<LocatedSpec>, ParserError> {
+  parse_spanned_specification(source).map(
+    |specs| specs.into_iter().map(
+      |(span, spec)| (SourceLocation::from_offset(source, span.start), spec)
+    ).collect())
+}
+
+/// Parses a complete .cog source, keeping the span of each statement.
+pub fn parse_spanned_specification(source: &[u8]) -> Result<Vec<SpannedSpec>, ParserError> {
+  use self::ParserError::*;
+
+  // The parser takes its text straight from the source, so it must
+  // only ever see valid UTF-8
+  if let Err(e) = str::from_utf8(source) {
+    return Err(InvalidUtf8(SourceLocation::from_offset(source, e.valid_up_to())));
+  }
+
+  let nom_result = nodes::located_graph_spec(source);
+  match nom_result {
+    IResult::Done(rem, _) if !is_blank(rem) => {
+      // Point at the statement that couldn't be parsed rather
+      // than the blank lines leading up to it
+      let skipped = rem.iter().position(|b| !is_blank_byte(*b)).unwrap_or(rem.len());
+      Err(NotAllParsed(SourceLocation::from_remaining(source, &rem[skipped..])))
     }
+    IResult::Done(_, out) =>
+      Ok(out.into_iter().map(
+        |(start, end, spec)| (source.len() - start.len()..source.len() - end.len(), spec)
+      ).collect()),
+    IResult::Incomplete(_) => Err(NomIncomplete(SourceLocation::from_offset(source, source.len()))),
+    IResult::Error(err) => Err(ParserError::from_nom_err(source, err)),
+  }
+}
+
+fn is_blank_byte(b: u8) -> bool {
+  b == b' ' || b == b'\t' || b == b'\r' || b == b'\n'
+}
+
+fn is_blank(input: &[u8]) -> bool {
+  input.iter().all(|b| is_blank_byte(*b))
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use std::io::Cursor;
+
+  #[test]
+  fn unparsed_statement_location() {
+    let source = &b"box text a\n  hi\n\n  bogus statement\n"[..];
+    match parse_specification(source) {
+      Err(ParserError::NotAllParsed(loc)) => {
+        assert_eq!(loc.line, 4);
+        assert_eq!(loc.column, 3);
+        assert_eq!(loc.line_text, "  bogus statement");
+      }
+      other => panic!("Expected NotAllParsed, got {:?}", other)
+    }
+  }
+
+  #[test]
+  fn statement_locations() {
+    let source = &b"# comment\nconnection a b\n\n  box text a\n  hi\n"[..];
+    let specs = parse_located_specification(source).unwrap();
+    assert_eq!(specs.len(), 2);
+    assert_eq!((specs[0].0.line, specs[0].0.column), (2, 1));
+    assert_eq!((specs[1].0.line, specs[1].0.column), (4, 3));
+  }
+
+  #[test]
+  fn drivers_for_readers_and_strings() {
+    let source = "box text a\n  hi\n\nconnection a a\n";
+    let from_str = StrDriver::new(source).read_to_specification().unwrap();
+    let from_reader = ReaderDriver::new(Cursor::new(source.as_bytes())).read_to_specification().unwrap();
+    assert_eq!(from_str.len(), 2);
+    assert_eq!(from_str, from_reader);
+
+    match StrDriver::new("box text\n").read_to_located_specification() {
+      Err(ref e) => assert_eq!(e.location().map(|l| l.line), Some(1)),
+      Ok(_) => panic!("Expected a parse error")
+    }
+  }
+
+  #[test]
+  fn invalid_utf8_is_an_error() {
+    let source = &b"box text a\n  caf\xe9\n"[..];
+    match parse_specification(source) {
+      Err(ParserError::InvalidUtf8(loc)) => assert_eq!((loc.line, loc.column), (2, 6)),
+      other => panic!("Expected InvalidUtf8, got {:?}", other)
+    }
+  }
+
+  #[test]
+  fn trailing_newlines_are_allowed() {
+    let source = &b"box text a\n  hi\n\n"[..];
+    assert_eq!(parse_specification(source).unwrap().len(), 1);
   }
 }
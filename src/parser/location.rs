@@ -0,0 +1,80 @@
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+/// A position in a source file, along with the text of the line it is on
+/// so that it can be shown to the user.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceLocation {
+  /// Line number, starting at 1
+  pub line: usize,
+  /// Column number in characters, starting at 1
+  pub column: usize,
+  /// The full text of the line, without its line ending
+  pub line_text: String
+}
+
+impl SourceLocation {
+  /// Finds the location of the byte at `offset` in `source`.
+  ///
+  /// Offsets past the end of the source refer to the end of the last line.
+  pub fn from_offset(source: &[u8], offset: usize) -> SourceLocation {
+    let offset = ::std::cmp::min(offset, source.len());
+    let line_start =
+      source[..offset].iter().rposition(|b| *b == b'\n').map(|i| i + 1).unwrap_or(0);
+    let line_end =
+      source[offset..].iter().position(|b| *b == b'\n' || *b == b'\r')
+        .map(|i| i + offset).unwrap_or(source.len());
+
+    let before = String::from_utf8_lossy(&source[line_start..offset]);
+
+    SourceLocation{
+      line: source[..line_start].iter().filter(|b| **b == b'\n').count() + 1,
+      column: before.chars().count() + 1,
+      line_text: String::from_utf8_lossy(&source[line_start..line_end]).into_owned()
+    }
+  }
+
+  /// Finds the location where `remaining` starts, where `remaining`
+  /// is a suffix of `source`.
+  pub fn from_remaining(source: &[u8], remaining: &[u8]) -> SourceLocation {
+    SourceLocation::from_offset(source, source.len() - remaining.len())
+  }
+
+  /// The source line followed by a caret pointing at the column.
+  pub fn snippet(&self) -> String {
+    let padding:String =
+      self.line_text.chars().take(self.column - 1)
+        .map(|c| if c == '\t' {'\t'} else {' '}).collect();
+    format!("{}\n{}^", self.line_text, padding)
+  }
+}
+
+impl Display for SourceLocation {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    write!(f, "line {}, column {}", self.line, self.column)
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::SourceLocation;
+
+  #[test]
+  fn location_of_offset() {
+    let source = &b"box text a\n  hello\nbad line"[..];
+    let loc = SourceLocation::from_offset(source, 21);
+    assert_eq!(loc.line, 3);
+    assert_eq!(loc.column, 3);
+    assert_eq!(loc.line_text, "bad line");
+    assert_eq!(loc.snippet(), "bad line\n  ^");
+  }
+
+  #[test]
+  fn location_at_end() {
+    let source = &b"box\n"[..];
+    let loc = SourceLocation::from_offset(source, 10);
+    assert_eq!(loc.line, 2);
+    assert_eq!(loc.column, 1);
+    assert_eq!(loc.line_text, "");
+  }
+}
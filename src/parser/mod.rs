@@ -1,4 +1,5 @@
 pub mod driver;
+pub mod location;
 pub mod nodes;
 
 pub type CustomNomErr = u32;
@@ -1,28 +1,142 @@
-use nom::{not_line_ending, line_ending, space, alphanumeric};
+use nom::{not_line_ending, line_ending, space, alphanumeric, Err, ErrorKind, IResult};
 
 use collections::borrow::ToOwned;
 
 use data::*;
 
-named! (box_spec <&[u8], BlockSpec>,
+fn box_spec(input: &[u8]) -> IResult<&[u8], BlockSpec> {
+  let indent = input.iter().take_while(|&&c| c == b' ' || c == b'\t').count();
+  chain!(input,
+    header: box_header              ~
+    gap?                            ~
+    line_comment?                   ~
+    line_ending                     ~
+    many0!(comment_line)            ~
+    text: apply!(box_text, indent)  ,
+    ||{BlockSpec::Boxed(header.0, header.1, text)}
+  )
+}
+
+named! (box_header <&[u8], (String, Coloring)>,
   chain!(
-    space?                      ~
+    gap?                        ~
     tag!("box")                 ~
-    space                       ~
+    gap                         ~
     tag!("text")                ~
-    space                       ~
+    gap                         ~
     name: ident_str             ~
-    coloring: defaulted_color   ~
-    space?                      ~
-    line_ending                 ~
-    text_line: not_line_ending  ,
-    ||{BlockSpec::Boxed(
-        name,
-        coloring,
-        (&String::from_utf8(text_line.to_owned()).unwrap()).trim().to_owned())}
+    coloring: defaulted_color   ,
+    ||(name, coloring)
   )
 );
 
+// The text of a box is either a `"""` heredoc or a paragraph: the line
+// after the header plus the non-blank lines directly below it that are
+// indented further than the header, which is `indent` spaces and tabs in.
+// A blank line or a line indented no further than the header ends it.
+// Line breaks are kept as '\n' in the resulting string.
+fn box_text(input: &[u8], indent: usize) -> IResult<&[u8], String> {
+  alt!(input, heredoc_text | apply!(paragraph_text, indent))
+}
+
+fn paragraph_text(input: &[u8], indent: usize) -> IResult<&[u8], String> {
+  chain!(input,
+    first: not_line_ending                              ~
+    rest: many0!(apply!(continuation_line, indent))     ,
+    ||{
+      let mut lines = vec![utf8_str(first).trim().to_owned()];
+      lines.extend(rest);
+      lines.join("\n")
+    }
+  )
+}
+
+fn continuation_line(input: &[u8], indent: usize) -> IResult<&[u8], String> {
+  let line =
+    chain!(input,
+      complete!(line_ending)  ~
+      lead: space             ~
+      text: not_line_ending   ,
+      ||(lead, text));
+  match line {
+    IResult::Done(rest, (lead, text)) => {
+      let continues = lead.iter().take_while(|&&c| c == b' ' || c == b'\t').count() > indent;
+      match non_blank_line(text) {
+        Some(text) if continues => IResult::Done(rest, text),
+        _ => IResult::Error(Err::Position(ErrorKind::MapOpt, input))
+      }
+    }
+    IResult::Error(e) => IResult::Error(e),
+    IResult::Incomplete(n) => IResult::Incomplete(n)
+  }
+}
+
+named! (heredoc_text <&[u8], String>,
+  chain!(
+    space?                                                ~
+    complete!(tag!("\"\"\""))                              ~
+    space?                                                ~
+    complete!(line_ending)                                ~
+    body: complete!(take_until_and_consume!("\"\"\""))     ,
+    || heredoc_lines(body)));
+
+/// Text from the source. The driver checks that the whole source is UTF-8
+/// before parsing, and the parser only splits it at ASCII characters.
+fn utf8_str(bytes: &[u8]) -> String {
+  String::from_utf8(bytes.to_owned()).unwrap()
+}
+
+fn non_blank_line(line: &[u8]) -> Option<String> {
+  let text = utf8_str(line).trim().to_owned();
+  if text.is_empty() {
+    None
+  } else {
+    Some(text)
+  }
+}
+
+/// Turns the body of a heredoc into box text.
+///
+/// When the closing quotes are on a line of their own, their indentation
+/// is removed from the start of every line, so that text can keep
+/// indentation shared by all of its lines. When they follow text, the
+/// indentation shared by every line is removed instead.
+fn heredoc_lines(body: &[u8]) -> String {
+  let body = utf8_str(body);
+  let mut lines:Vec<&str> = body.split('\n').collect();
+  let closing = lines.pop().unwrap_or("");
+  if !closing.chars().all(|c| c == ' ' || c == '\t') {
+    lines.push(closing);
+    let lines:Vec<&str> = lines.iter().map(|l| l.trim_right()).collect();
+    return remove_common_indent(&lines);
+  }
+
+  lines.iter()
+    .map(|l| {
+      let l = l.trim_right();
+      let indent = l.len() - l.trim_left_matches(|c| c == ' ' || c == '\t').len();
+      &l[::std::cmp::min(indent, closing.len())..]
+    })
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
+/// Joins lines with '\n', removing the indentation shared by every line
+/// that isn't empty. Only spaces and tabs count as indentation.
+pub fn remove_common_indent(lines: &[&str]) -> String {
+  let indent =
+    lines.iter()
+      .filter(|l| !l.is_empty())
+      .map(|l| l.len() - l.trim_left_matches(|c| c == ' ' || c == '\t').len())
+      .min()
+      .unwrap_or(0);
+
+  lines.iter()
+    .map(|l| if l.is_empty() {*l} else {&l[indent..]})
+    .collect::<Vec<_>>()
+    .join("\n")
+}
+
 named! (color_select <&[u8], Coloring>,
     alt!(
         tag!("red") => { |_| Coloring::Red } |
@@ -46,7 +160,7 @@ named! (coloring_spec <&[u8], Coloring>,
 
 named! (defaulted_color <&[u8], Coloring>,
   chain!(
-    c: preceded!(space, coloring_spec)?,
+    c: preceded!(gap, coloring_spec)?,
     ||c.unwrap_or(Coloring::Default)));
 
 named! (conn_type_spec <&[u8], ConnectionType>,
@@ -56,21 +170,38 @@ named! (conn_type_spec <&[u8], ConnectionType>,
 
 named! (connection_spec<&[u8], Connection>,
   chain!(
-    space?                                    ~
-    ct: terminated!(conn_type_spec,space)?    ~
+    gap?                                      ~
+    ct: terminated!(conn_type_spec,gap)?      ~
     tag!("connection")                        ~
-    space                                     ~
+    gap                                       ~
     first: ident_str                          ~
-    space                                     ~
+    gap                                       ~
     second: ident_str                         ~
-    color: defaulted_color,
+    color: defaulted_color                    ~
+    label: preceded!(gap, label_spec)?        ,
     || Connection{ty: ct.unwrap_or(ConnectionType::Generic),
                   start: first.to_owned(),
                   end: second.to_owned(),
-                  color:color}
+                  color:color,
+                  label: label}
   )
 );
 
+named! (label_spec <&[u8], String>,
+  chain!(
+    tag!("label")       ~
+    gap                 ~
+    text: quoted_str    ,
+    ||text));
+
+// Text between double quotes, which can't contain quotes or line breaks
+named! (quoted_str <&[u8], String>,
+  chain!(
+    complete!(tag!("\""))        ~
+    text: is_not!("\"\r\n")      ~
+    complete!(tag!("\""))        ,
+    ||utf8_str(text)));
+
 named! (ident_str <&[u8], String>,
   chain!(
     data: alphanumeric,
@@ -84,18 +215,102 @@ named! (data_spec <&[u8], DataSpec>,
 
 pub type DataVec = Vec<DataSpec>;
 
+/// A statement along with the input starting at its first token and the
+/// input following it and any comments after it on its last line, which
+/// are used to work out where the statement is in the source.
+pub type LocatedData<'a> = (&'a [u8], &'a [u8], DataSpec);
+
+fn located_data_spec<'a>(input: &'a [u8]) -> IResult<&'a [u8], LocatedData<'a>> {
+  let start =
+    match gap(input) {
+      IResult::Done(rest, _) => rest,
+      _ => input
+    };
+  match data_spec(input) {
+    IResult::Done(rest, spec) => {
+      let end =
+        match statement_trailer(rest) {
+          IResult::Done(after, _) => after,
+          _ => rest
+        };
+      IResult::Done(rest, (start, end, spec))
+    }
+    IResult::Error(e) => IResult::Error(e),
+    IResult::Incomplete(n) => IResult::Incomplete(n)
+  }
+}
+
+// A comment running to the end of the line, started by `#` or `//`
+named! (line_comment <&[u8], ()>,
+  chain!(
+    complete!(alt!(tag!("#") | tag!("//"))) ~
+    not_line_ending,
+    ||()));
+
+// A comment between `/*` and `*/`, which may span several lines
+named! (block_comment <&[u8], ()>,
+  chain!(
+    complete!(tag!("/*"))                      ~
+    complete!(take_until_and_consume!("*/")),
+    ||()));
+
+// Whitespace within a line. Block comments count as whitespace.
+named! (gap <&[u8], ()>,
+  chain!(
+    many1!(alt!(chain!(space, ||()) | block_comment)),
+    ||()));
+
+// Whitespace and comments following a statement on its last line
+named! (statement_trailer <&[u8], ()>,
+  chain!(
+    gap?            ~
+    line_comment?   ,
+    ||()));
+
+// A line with nothing but whitespace and comments on it
+named! (blank_line <&[u8], ()>,
+  chain!(
+    gap?                    ~
+    line_comment?           ~
+    complete!(line_ending)  ,
+    ||()));
+
+// A line holding at least one comment and nothing else.
+// These may appear between a box header and its text line.
+named! (comment_line <&[u8], ()>,
+  chain!(
+    space?                                ~
+    alt!(line_comment | block_comment)    ~
+    gap?                                  ~
+    line_comment?                         ~
+    complete!(line_ending)                ,
+    ||()));
+
 named! (eol_space_spec < &[u8], Vec<()> >,
-    many1!(chain!(space? ~ line_ending, || ())));
+    many1!(blank_line));
+
+named! (pub located_graph_spec <&[u8], Vec<LocatedData> >,
+  chain!(
+    many0!(blank_line)                                        ~
+    specs: separated_list!(eol_space_spec, located_data_spec) ~
+    many0!(blank_line)                                        ~
+    gap?                                                      ~
+    line_comment?                                             ,
+    ||specs)
+);
 
 named! (pub full_graph_spec <&[u8], DataVec>,
-  separated_list!(eol_space_spec, data_spec)
+  chain!(
+    specs: located_graph_spec,
+    ||specs.into_iter().map(|(_, _, spec)| spec).collect())
 );
 
 #[cfg(test)]
 mod test{
   use collections::borrow::ToOwned;
-  use super::{box_spec, connection_spec};
+  use super::{box_spec, connection_spec, full_graph_spec};
   use ::data::*;
+  use ::data::fixtures::conn;
   use nom::IResult::Done;
   #[test]
   fn black_box_spec() {
@@ -117,19 +332,22 @@ mod test{
                  ty:ConnectionType::Generic,
                  start:"a".to_owned(),
                  end:"b".to_owned(),
-                 color: Coloring::Default}));
+                 color: Coloring::Default,
+                 label: None}));
     assert_eq!(connection_spec(in2),
                Done(&b""[..], Connection{
                  ty:ConnectionType::Dual,
                  start:"foo".to_owned(),
                  end:"bar".to_owned(),
-                 color: Coloring::Red}));
+                 color: Coloring::Red,
+                 label: None}));
     assert_eq!(connection_spec(no_ty_spec),
                Done(&b"\n"[..], Connection{
                  ty:ConnectionType::Generic,
                  start:"foo".to_owned(),
                  end:"bar".to_owned(),
-                 color: Coloring::Default}));
+                 color: Coloring::Default,
+                 label: None}));
 
   }
 
@@ -144,4 +362,149 @@ mod test{
         super::defaulted_color(input),
         Done(&b""[..],Coloring::Red));
   }
+
+  #[test]
+  fn line_comments_between_statements() {
+    let input = &b"# leading comment\n\
+                   // another\n\
+                   generic connection a b\n\
+                   \n\
+                   # between statements\n\
+                   \n\
+                   dual connection b a\n"[..];
+    assert_eq!(full_graph_spec(input),
+               Done(&b""[..], vec![conn(ConnectionType::Generic, "a", "b", Coloring::Default, None),
+                                   conn(ConnectionType::Dual, "b", "a", Coloring::Default, None)]));
+  }
+
+  #[test]
+  fn trailing_line_comments() {
+    let input = &b"connection a b # after a connection\n\
+                   box text c color red // after a header\n\
+                   Some text\n\
+                   # final comment without newline"[..];
+    assert_eq!(full_graph_spec(input),
+               Done(&b""[..], vec![
+                 conn(ConnectionType::Generic, "a", "b", Coloring::Default, None),
+                 DataSpec::BlockDataSpec(
+                   BlockSpec::Boxed("c".to_owned(), Coloring::Red, "Some text".to_owned()))]));
+  }
+
+  #[test]
+  fn comments_between_header_and_text() {
+    let input = &b"box text tester\n  # about the text\n  /* more */\n  The text"[..];
+    assert_eq!(box_spec(input),
+               Done(&b""[..],
+                    BlockSpec::Boxed("tester".to_owned(),
+                                     Coloring::Default,
+                                     "The text".to_owned())));
+  }
+
+  #[test]
+  fn block_comments_between_tokens() {
+    let input = &b"singular /* kind */ connection /* from */ a b /* colour */ color cyan"[..];
+    assert_eq!(connection_spec(input),
+               Done(&b""[..], Connection{
+                 ty:ConnectionType::Singular,
+                 start:"a".to_owned(),
+                 end:"b".to_owned(),
+                 color: Coloring::Cyan,
+                 label: None}));
+  }
+
+  #[test]
+  fn multi_line_block_comment() {
+    let input = &b"connection a b\n/* spans\n   several lines */\nconnection b a\n"[..];
+    assert_eq!(full_graph_spec(input),
+               Done(&b""[..], vec![conn(ConnectionType::Generic, "a", "b", Coloring::Default, None),
+                                   conn(ConnectionType::Generic, "b", "a", Coloring::Default, None)]));
+  }
+
+  #[test]
+  fn indented_paragraph_box_text() {
+    let input = &b"box text svc\n  Service\n  port 80\n  port 443\n\nconnection svc svc"[..];
+    assert_eq!(box_spec(input),
+               Done(&b"\n\nconnection svc svc"[..],
+                    BlockSpec::Boxed("svc".to_owned(),
+                                     Coloring::Default,
+                                     "Service\nport 80\nport 443".to_owned())));
+  }
+
+  #[test]
+  fn unindented_line_ends_paragraph() {
+    let input = &b"box text a\n  first\nconnection a b"[..];
+    assert_eq!(box_spec(input),
+               Done(&b"\nconnection a b"[..],
+                    BlockSpec::Boxed("a".to_owned(), Coloring::Default, "first".to_owned())));
+  }
+
+  #[test]
+  fn indented_lines_stay_in_paragraph() {
+    let input = &b"box text a\n  Links\n  generic connection a b\n\nconnection a b\n"[..];
+    assert_eq!(full_graph_spec(input),
+               Done(&b""[..], vec![
+                 DataSpec::BlockDataSpec(
+                   BlockSpec::Boxed("a".to_owned(), Coloring::Default, "Links\ngeneric connection a b".to_owned())),
+                 conn(ConnectionType::Generic, "a", "b", Coloring::Default, None)]));
+  }
+
+  #[test]
+  fn paragraph_ends_at_header_indentation() {
+    let input = &b"  box text a\n  Text\n    more\n  connection a b\n  box text b\n    B\n"[..];
+    assert_eq!(full_graph_spec(input),
+               Done(&b""[..], vec![
+                 DataSpec::BlockDataSpec(
+                   BlockSpec::Boxed("a".to_owned(), Coloring::Default, "Text\nmore".to_owned())),
+                 conn(ConnectionType::Generic, "a", "b", Coloring::Default, None),
+                 DataSpec::BlockDataSpec(
+                   BlockSpec::Boxed("b".to_owned(), Coloring::Default, "B".to_owned()))]));
+  }
+
+  #[test]
+  fn heredoc_box_text() {
+    let input = &b"box text svc color green\n  \"\"\"\n  Service\n\n    - port 80\n  \"\"\"\n"[..];
+    assert_eq!(box_spec(input),
+               Done(&b"\n"[..],
+                    BlockSpec::Boxed("svc".to_owned(),
+                                     Coloring::Green,
+                                     "Service\n\n  - port 80".to_owned())));
+  }
+
+  #[test]
+  fn heredoc_keeps_indentation_beyond_closing_quotes() {
+    let input = &b"box text a\n\"\"\"\n  - one\n  - two\n\"\"\"\n"[..];
+    assert_eq!(box_spec(input),
+               Done(&b"\n"[..],
+                    BlockSpec::Boxed("a".to_owned(), Coloring::Default, "  - one\n  - two".to_owned())));
+  }
+
+  #[test]
+  fn heredoc_with_other_whitespace() {
+    let input = "box text a\n  \"\"\"\n\u{3000}wide\n  narrow\n  \"\"\"\n".as_bytes();
+    assert_eq!(box_spec(input),
+               Done(&b"\n"[..],
+                    BlockSpec::Boxed("a".to_owned(),
+                                     Coloring::Default,
+                                     "\u{3000}wide\nnarrow".to_owned())));
+  }
+
+  #[test]
+  fn connection_labels() {
+    let input = &b"singular connection web db color red label \"SQL over TLS\"\n"[..];
+    let no_color = &b"connection web db label \"gRPC\""[..];
+    assert_eq!(connection_spec(input),
+               Done(&b"\n"[..], Connection{
+                 ty:ConnectionType::Singular,
+                 start:"web".to_owned(),
+                 end:"db".to_owned(),
+                 color: Coloring::Red,
+                 label: Some("SQL over TLS".to_owned())}));
+    assert_eq!(connection_spec(no_color),
+               Done(&b""[..], Connection{
+                 ty:ConnectionType::Generic,
+                 start:"web".to_owned(),
+                 end:"db".to_owned(),
+                 color: Coloring::Default,
+                 label: Some("gRPC".to_owned())}));
+  }
 }
@@ -0,0 +1,119 @@
+use data::{BlockSpec, Coloring};
+use layout::charset::CharSet;
+use layout::display::{BlockDisplay, ConnectionDisplay};
+use libc;
+use std::env;
+use super::text::rasterize;
+
+const RESET: &'static str = "\x1b[0m";
+
+/// Renders a laid out diagram to text coloured with ANSI escape codes.
+///
+/// Colours are the same as in the ncurses view: each colour on a black
+/// background, with black and the default colour left unstyled. Without
+/// `color` the output is the same as the plain text renderer's.
+pub fn render_to_string(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet,
+  color: bool)
+    -> String {
+  let grid = rasterize(blocks, connections, charset);
+
+  let mut out = String::new();
+  for row in grid.rows().iter() {
+    let len = row.iter().rposition(|cell| cell.character != ' ').map(|i| i + 1).unwrap_or(0);
+    let mut current = None;
+    for cell in row[..len].iter() {
+      let code = if color {sgr_code(cell.color)} else {None};
+      if code != current {
+        out.push_str(code.unwrap_or(RESET));
+        current = code;
+      }
+      out.push(cell.character);
+    }
+    if current.is_some() {
+      out.push_str(RESET);
+    }
+    out.push('\n');
+  }
+  out
+}
+
+/// Prints a laid out diagram to stdout, coloured if `color_enabled`.
+pub fn print_diagram(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet) {
+  print!("{}", render_to_string(blocks, connections, charset, color_enabled()));
+}
+
+/// Whether coloured output should be written to stdout: it must be a
+/// terminal, and `NO_COLOR` must not be set.
+pub fn color_enabled() -> bool {
+  let no_color = env::var_os("NO_COLOR").map(|v| !v.is_empty()).unwrap_or(false);
+  !no_color && unsafe { libc::isatty(libc::STDOUT_FILENO) != 0 }
+}
+
+/// The escape selecting the colour pair `init_default_color_pairs`
+/// sets up for a coloring, if it has one.
+fn sgr_code(color: Coloring) -> Option<&'static str> {
+  use data::Coloring::*;
+  match color {
+    Default | Black => None,
+    White => Some("\x1b[37;40m"),
+    Red => Some("\x1b[31;40m"),
+    Green => Some("\x1b[32;40m"),
+    Yellow => Some("\x1b[33;40m"),
+    Blue => Some("\x1b[34;40m"),
+    Magenta => Some("\x1b[35;40m"),
+    Cyan => Some("\x1b[36;40m")
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::render_to_string;
+  use data::{BlockSpec, Coloring};
+  use layout::charset::CharSet;
+  use layout::display::*;
+  use render::text;
+
+  fn diagram() -> (BlockSpec, BlockDisplay, ConnectionDisplay) {
+    let spec = BlockSpec::Boxed("a".to_owned(), Coloring::Red, "hi".to_owned());
+    let block = BlockDisplay{
+      color: Coloring::Red,
+      content_lines: vec!["hi".to_owned()],
+      pos: Position{x:0, y:0},
+      size: Size{width:6, height:3}};
+    let conn = ConnectionDisplay{
+      parts: vec![ConnectionPart{
+        start: Position{x:3, y:2},
+        end: Position{x:3, y:4}}],
+      color: Coloring::Blue,
+      start_arrow: false,
+      end_arrow: true,
+      label: None};
+    (spec, block, conn)
+  }
+
+  #[test]
+  fn colours_each_run() {
+    let (spec, block, conn) = diagram();
+    let out = render_to_string(&[(&spec, block)], &[conn], CharSet::Ascii, true);
+    assert_eq!(out,
+      "\x1b[31;40m+----+\x1b[0m\n\
+       \x1b[31;40m|\x1b[0m \x1b[31;40mhi\x1b[0m \x1b[31;40m|\x1b[0m\n\
+       \x1b[31;40m+--\x1b[34;40m#\x1b[31;40m-+\x1b[0m\n\
+       \x20  \x1b[34;40m|\x1b[0m\n\
+       \x20  \x1b[34;40mv\x1b[0m\n");
+  }
+
+  #[test]
+  fn without_colour_matches_text() {
+    let (spec, block, conn) = diagram();
+    let plain = text::render_to_string(&[(&spec, block)], &[conn], CharSet::Ascii);
+    let (spec, block, conn) = diagram();
+    assert_eq!(render_to_string(&[(&spec, block)], &[conn], CharSet::Ascii, false), plain);
+  }
+}
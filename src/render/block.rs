@@ -1,33 +1,41 @@
+use layout::charset::CharSet;
 use layout::display::BlockDisplay;
+use layout::display::BlockCorner::*;
 use layout::display::Position;
-use ncurses::mvprintw;
-use super::CharPositioner;
+use data::Coloring;
+use super::Canvas;
 
-pub fn draw_block_display(
+pub fn draw_block_display<C: Canvas>(
+  canvas: &mut C,
   offset: Position,
-  block: BlockDisplay) {
+  block: &BlockDisplay,
+  charset: CharSet) {
   
   let top_left = offset + block.pos;
+  let right = block.size.width - 1;
+  let bottom = block.size.height - 1;
 
-  block.color.color_on();
+  canvas.set_style(block.color);
   
-  for corner in block.corners() {
-    corner.place_char('+');
+  // corners() goes through the corners in this order
+  let corner_kinds = [TopLeft, TopRight, BottomLeft, BottomRight];
+  for (kind, corner) in corner_kinds.iter().zip(block.corners()) {
+    canvas.place_char(corner + offset, charset.corner(*kind));
   }
 
   for i in 1..(block.size.height-1) {
-    top_left.add_y(i).place_char('|');
-    top_left.add_y(i).add_x(block.size.width-1).place_char('|');
+    canvas.place_char(top_left.add_y(i), charset.line(true));
+    canvas.place_char(top_left.add_y(i).add_x(right), charset.line(true));
   }
   for i in 1..(block.size.width-1) {
-    top_left.add_x(i).place_char('-');
-    top_left.add_x(i).add_y(block.size.height-1).place_char('-');
+    canvas.place_char(top_left.add_x(i), charset.line(false));
+    canvas.place_char(top_left.add_x(i).add_y(bottom), charset.line(false));
   }
 
   for (i, content) in (0u32..).zip(block.content_lines.iter()) {
     let start_pos = top_left.add_y(i + 1).add_x(2);
-    mvprintw(start_pos.y as i32, start_pos.x as i32, content);
+    canvas.write_str(start_pos, content);
   }
 
-  block.color.color_off();
+  canvas.set_style(Coloring::Default);
 }
@@ -1,19 +1,20 @@
+use layout::charset::CharSet;
 use layout::display::ConnectionDisplay;
 use layout::display::Position;
-use ncurses::mvprintw;
-use super::CharPositioner;
+use data::Coloring;
+use super::Canvas;
 
-pub fn draw_connection(
+pub fn draw_connection<C: Canvas>(
+  canvas: &mut C,
   offset: Position,
-  connection: &ConnectionDisplay) {
+  connection: &ConnectionDisplay,
+  charset: CharSet) {
 
-  connection.color.color_on();
+  canvas.set_style(connection.color);
   
-  let mut first_drawn = false;
-  let mut end:Option<Position> = None;
-
   for part in connection.parts.iter() {
-    if part.start.x == part.end.x {
+    let line = charset.line(part.is_vertical());
+    if part.is_vertical() {
       let (upper, lower) = 
         if part.end.y > part.start.y {
           (part.start, part.end)
@@ -21,7 +22,7 @@ pub fn draw_connection(
           (part.end, part.start)
         };
       for i in 0..(lower.y - upper.y) {
-        (upper + offset).add_y(i).place_char(part.internal_character);
+        canvas.place_char((upper + offset).add_y(i), line);
       }
     } else {
       let (left, right) =
@@ -31,22 +32,35 @@ pub fn draw_connection(
           (part.end, part.start)
         };
       for i in 0..(right.x - left.x) {
-        (left + offset).add_x(i).place_char(part.internal_character);
+        canvas.place_char((left + offset).add_x(i), line);
       }
     }
-    if first_drawn {
-      (part.start + offset).place_char(connection.part_end_char);
-    } else {
-      (part.start + offset).place_char(connection.total_start_char);
-      first_drawn = true;
-    };
-    (offset + part.end).place_char(connection.part_end_char);
-    end = Some(part.end);
   }
 
-  for pos in end {
-    (pos + offset).place_char(connection.total_end_char);
+  // Each part ends where the next one starts, so the bends are the
+  // ends of every part but the last
+  for adjacent in connection.parts.windows(2) {
+    let bend =
+      charset.elbow(adjacent[0].direction(), adjacent[1].direction())
+        .unwrap_or(charset.bend());
+    canvas.place_char(adjacent[0].end + offset, bend);
+  }
+
+  // Arrowheads point into the block at each end, so the one at the
+  // start points against the direction of the first part
+  if let (Some(first), Some(last)) = (connection.parts.first(), connection.parts.last()) {
+    let (x, y) = first.direction();
+    let start = if connection.start_arrow {charset.arrow((-x, -y))} else {charset.endpoint()};
+    let end = if connection.end_arrow {charset.arrow(last.direction())} else {charset.endpoint()};
+    canvas.place_char(first.start + offset, start);
+    canvas.place_char(last.end + offset, end);
+  }
+
+  if let Some(ref label) = connection.label {
+    for pos in label.pos {
+      canvas.write_str(pos + offset, &label.text);
+    }
   }
 
-  connection.color.color_off();
+  canvas.set_style(Coloring::Default);
 }
@@ -0,0 +1,75 @@
+use data::Coloring;
+use layout::display::Position;
+use ncurses::*;
+use super::Canvas;
+
+/// Canvas that draws directly to the ncurses standard screen.
+pub struct NcursesCanvas {
+  current: Coloring
+}
+
+impl NcursesCanvas {
+  /// Creates a canvas for the standard screen. ncurses must already
+  /// have been initialized with colour support.
+  pub fn new() -> NcursesCanvas {
+    NcursesCanvas{current: Coloring::Default}
+  }
+}
+
+impl Canvas for NcursesCanvas {
+  fn place_char(&mut self, pos: Position, character: char) {
+    // chtype only has room for a byte of text next to its attributes, so
+    // box-drawing characters are written as UTF-8 strings instead.
+    if (character as u32) < 128 {
+      mvaddch(pos.y as i32, pos.x as i32, character as chtype);
+    } else {
+      mvaddstr(pos.y as i32, pos.x as i32, &character.to_string());
+    }
+  }
+
+  fn write_str(&mut self, pos: Position, text: &str) {
+    mvprintw(pos.y as i32, pos.x as i32, text);
+  }
+
+  fn set_style(&mut self, color: Coloring) {
+    for c in ncurses_color(self.current) {
+      attroff(COLOR_PAIR(c));
+    }
+    for c in ncurses_color(color) {
+      attron(COLOR_PAIR(c));
+    }
+    self.current = color;
+  }
+}
+
+pub fn ncurses_color(color: Coloring) -> Option<i16> {
+  use data::Coloring::*;
+  match color {
+    Default => None,
+    Black => Some(COLOR_BLACK),
+    White => Some(COLOR_WHITE),
+    Red => Some(COLOR_RED),
+    Green => Some(COLOR_GREEN),
+    Yellow => Some(COLOR_YELLOW),
+    Blue => Some(COLOR_BLUE),
+    Magenta => Some(COLOR_MAGENTA),
+    Cyan => Some(COLOR_CYAN)
+  }
+}
+
+pub fn init_default_color_pairs() {
+  use data::Coloring::*;
+  init_color_pair(White, Black);
+  init_color_pair(Red, Black);
+  init_color_pair(Green, Black);
+  init_color_pair(Yellow, Black);
+  init_color_pair(Blue, Black);
+  init_color_pair(Magenta, Black);
+  init_color_pair(Cyan, Black);
+}
+
+fn init_color_pair(color_one:Coloring, color_two:Coloring) {
+  let c_one = ncurses_color(color_one).unwrap();
+  let c_two = ncurses_color(color_two).unwrap();
+  init_pair(c_one, c_one, c_two);
+}
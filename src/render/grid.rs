@@ -0,0 +1,113 @@
+use data::Coloring;
+use layout::display::Position;
+use super::Canvas;
+
+/// A character in the grid and the colour it was drawn in.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Cell {
+  pub character: char,
+  pub color: Coloring
+}
+
+const BLANK: Cell = Cell{character: ' ', color: Coloring::Default};
+
+/// An in-memory grid of characters that diagrams can be rasterized into.
+///
+/// The grid grows as characters are placed, so callers don't need to know
+/// the size of the diagram up front. Each cell keeps the colour set when
+/// it was drawn, for renderers that can show colour.
+pub struct CharGrid {
+  rows: Vec<Vec<Cell>>,
+  current: Coloring
+}
+
+impl CharGrid {
+  pub fn new() -> CharGrid {
+    CharGrid{rows: vec![], current: Coloring::Default}
+  }
+
+  pub fn get_char(&self, pos: Position) -> Option<char> {
+    self.get_cell(pos).map(|cell| cell.character)
+  }
+
+  pub fn get_color(&self, pos: Position) -> Option<Coloring> {
+    self.get_cell(pos).map(|cell| cell.color)
+  }
+
+  fn get_cell(&self, pos: Position) -> Option<Cell> {
+    self.rows.get(pos.y as usize).and_then(|row| row.get(pos.x as usize)).cloned()
+  }
+
+  /// The cells of every row. Rows are only as long as the last cell
+  /// drawn on them.
+  pub fn rows(&self) -> &[Vec<Cell>] {
+    &self.rows
+  }
+
+  pub fn height(&self) -> usize {
+    self.rows.len()
+  }
+
+  /// The contents of the grid, one string per row, with trailing
+  /// whitespace removed.
+  pub fn lines(&self) -> Vec<String> {
+    self.rows.iter().map(
+      |row| row.iter().map(|cell| cell.character).collect::<String>().trim_right().to_owned()
+    ).collect()
+  }
+}
+
+impl Canvas for CharGrid {
+  fn place_char(&mut self, pos: Position, character: char) {
+    let (x, y) = (pos.x as usize, pos.y as usize);
+    while self.rows.len() <= y {
+      self.rows.push(vec![]);
+    }
+    let row = &mut self.rows[y];
+    while row.len() <= x {
+      row.push(BLANK);
+    }
+    row[x] = Cell{character: character, color: self.current};
+  }
+
+  fn set_style(&mut self, color: Coloring) {
+    self.current = color;
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use super::CharGrid;
+  use data::Coloring;
+  use render::Canvas;
+  use layout::display::Position;
+
+  #[test]
+  fn grid_grows_to_fit() {
+    let mut grid = CharGrid::new();
+    grid.place_char(Position{x:3, y:1}, '+');
+    assert_eq!(grid.height(), 2);
+    assert_eq!(grid.lines(), vec!["".to_owned(), "   +".to_owned()]);
+  }
+
+  #[test]
+  fn write_str_overwrites() {
+    let mut grid = CharGrid::new();
+    grid.write_str(Position{x:0, y:0}, "-----");
+    grid.write_str(Position{x:1, y:0}, "ab");
+    assert_eq!(grid.get_char(Position{x:2, y:0}), Some('b'));
+    assert_eq!(grid.lines(), vec!["-ab--".to_owned()]);
+  }
+
+  #[test]
+  fn cells_keep_their_colour() {
+    let mut grid = CharGrid::new();
+    grid.set_style(Coloring::Red);
+    grid.write_str(Position{x:1, y:0}, "ab");
+    grid.set_style(Coloring::Default);
+    grid.place_char(Position{x:2, y:0}, 'c');
+    assert_eq!(grid.get_color(Position{x:0, y:0}), Some(Coloring::Default));
+    assert_eq!(grid.get_color(Position{x:1, y:0}), Some(Coloring::Red));
+    assert_eq!(grid.get_color(Position{x:2, y:0}), Some(Coloring::Default));
+  }
+}
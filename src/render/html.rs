@@ -0,0 +1,124 @@
+use data::{BlockSpec, Coloring};
+use layout::charset::CharSet;
+use layout::display::{BlockDisplay, ConnectionDisplay};
+use super::css_color;
+use super::grid::Cell;
+use super::text::rasterize;
+
+/// Renders a laid out diagram as an HTML `<pre>` element.
+///
+/// The diagram is rasterized the same way as for plain text. Coloured
+/// characters are wrapped in `<span>`s styled with their colour, and the
+/// characters of each block carry a title naming the block, which
+/// browsers show on hover. The element can be pasted into any page.
+pub fn render_to_string(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet)
+    -> String {
+  let grid = rasterize(blocks, connections, charset);
+
+  let mut out = String::from("<pre class=\"cog\">\n");
+  for (y, row) in grid.rows().iter().enumerate() {
+    let len = row.iter().rposition(|cell| cell.character != ' ').map(|i| i + 1).unwrap_or(0);
+
+    // Blank cells take the colour of the cell before them in the same
+    // block, so that a block's padding doesn't break up its span.
+    let mut spans:Vec<(Coloring, Option<&str>)> = vec![];
+    for x in 0..len {
+      let block = block_at(blocks, x as u32, y as u32);
+      let color =
+        match spans.last() {
+          Some(&(previous, in_block)) if row[x].character == ' ' && in_block == block => previous,
+          _ => row[x].color
+        };
+      spans.push((color, block));
+    }
+
+    let mut x = 0;
+    while x < len {
+      let end = (x..len).find(|&i| spans[i] != spans[x]).unwrap_or(len);
+      write_span(&mut out, spans[x].0, spans[x].1, &row[x..end]);
+      x = end;
+    }
+    out.push('\n');
+  }
+  out.push_str("</pre>\n");
+  out
+}
+
+/// Prints a laid out diagram to stdout as an HTML `<pre>` element.
+pub fn print_diagram(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet) {
+  print!("{}", render_to_string(blocks, connections, charset));
+}
+
+/// The name of the block covering a grid cell, if any
+fn block_at<'a>(blocks: &[(&'a BlockSpec, BlockDisplay)], x: u32, y: u32) -> Option<&'a str> {
+  blocks.iter()
+    .find(|&&(_, ref block)|
+      x >= block.pos.x && x < block.pos.x + block.size.width &&
+      y >= block.pos.y && y < block.pos.y + block.size.height)
+    .map(|&(spec, _)| spec.get_name())
+}
+
+fn write_span(out: &mut String, color: Coloring, block: Option<&str>, cells: &[Cell]) {
+  let text:String = cells.iter().map(|cell| cell.character).collect();
+  if color == Coloring::Default && block.is_none() {
+    out.push_str(&escape(&text));
+    return;
+  }
+
+  out.push_str("<span");
+  if color != Coloring::Default {
+    out.push_str(&format!(" style=\"color: {}\"", css_color(color)));
+  }
+  for name in block {
+    out.push_str(&format!(" title=\"{}\"", escape(name)));
+  }
+  out.push_str(&format!(">{}</span>", escape(&text)));
+}
+
+fn escape(text: &str) -> String {
+  text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;").replace("\"", "&quot;")
+}
+
+#[cfg(test)]
+mod test {
+  use super::render_to_string;
+  use data::{BlockSpec, Coloring};
+  use layout::charset::CharSet;
+  use layout::display::*;
+
+  #[test]
+  fn colours_and_names_blocks() {
+    let spec = BlockSpec::Boxed("web".to_owned(), Coloring::Red, "<hi>".to_owned());
+    let block = BlockDisplay{
+      color: Coloring::Red,
+      content_lines: vec!["<hi>".to_owned()],
+      pos: Position{x:0, y:0},
+      size: Size{width:8, height:3}};
+    let conn = ConnectionDisplay{
+      parts: vec![ConnectionPart{
+        start: Position{x:3, y:2},
+        end: Position{x:3, y:4}}],
+      color: Coloring::Default,
+      start_arrow: false,
+      end_arrow: true,
+      label: Some(ConnectionLabel{text: "ok".to_owned(), pos: Some(Position{x:5, y:3})})};
+
+    let html = render_to_string(&[(&spec, block)], &[conn], CharSet::Ascii);
+    assert_eq!(html,
+      "<pre class=\"cog\">\n\
+       <span style=\"color: red\" title=\"web\">+------+</span>\n\
+       <span style=\"color: red\" title=\"web\">| &lt;hi&gt; |</span>\n\
+       <span style=\"color: red\" title=\"web\">+--</span>\
+       <span title=\"web\">#</span>\
+       <span style=\"color: red\" title=\"web\">---+</span>\n\
+       \x20  | ok\n\
+       \x20  v\n\
+       </pre>\n");
+  }
+}
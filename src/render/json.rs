@@ -0,0 +1,86 @@
+use data::{BlockSpec, Coloring};
+use layout::display::{BlockDisplay, ConnectionDisplay, Position, Size};
+use rustc_serialize::json;
+use super::diagram_size;
+
+#[derive(RustcEncodable)]
+struct Document<'a> {
+  size: Size,
+  blocks: Vec<NamedBlock<'a>>,
+  connections: &'a [ConnectionDisplay]
+}
+
+#[derive(RustcEncodable)]
+struct NamedBlock<'a> {
+  name: &'a str,
+  color: Coloring,
+  pos: Position,
+  size: Size,
+  content_lines: &'a [String]
+}
+
+/// Renders a laid out diagram as a JSON document, so that other tools
+/// can use the layout without drawing it the way cog does.
+///
+/// The document holds the `size` of the diagram, the `blocks` with their
+/// name, colour, position (`pos`), size and wrapped lines of text, and
+/// the `connections` in the order they were given, each with its colour,
+/// label, which ends have arrowheads and the straight parts that make
+/// it up.
+pub fn render_to_string(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay])
+    -> String {
+  let document = Document{
+    size: diagram_size(blocks, connections),
+    blocks: blocks.iter().map(|&(spec, ref block)| NamedBlock{
+      name: spec.get_name(),
+      color: block.color,
+      pos: block.pos,
+      size: block.size,
+      content_lines: &block.content_lines}).collect(),
+    connections: connections};
+  format!("{}\n", json::as_pretty_json(&document))
+}
+
+/// Prints a laid out diagram to stdout as a JSON document.
+pub fn print_diagram(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay]) {
+  print!("{}", render_to_string(blocks, connections));
+}
+
+#[cfg(test)]
+mod test {
+  use super::render_to_string;
+  use data::{BlockSpec, Coloring};
+  use layout::display::*;
+  use rustc_serialize::json::Json;
+
+  #[test]
+  fn writes_blocks_and_connections() {
+    let spec = BlockSpec::Boxed("a".to_owned(), Coloring::Red, "Hi".to_owned());
+    let blocks = vec![(&spec, BlockDisplay{
+      color: Coloring::Red,
+      content_lines: vec!["Hi".to_owned()],
+      pos: Position::new(0, 0),
+      size: Size{width: 6, height: 3}})];
+    let connections = vec![ConnectionDisplay{
+      parts: vec![ConnectionPart{start: Position::new(3, 3), end: Position::new(3, 6)}],
+      color: Coloring::Default,
+      start_arrow: false,
+      end_arrow: true,
+      label: Some(ConnectionLabel{text: "up".to_owned(), pos: None})}];
+
+    let json = Json::from_str(&render_to_string(&blocks, &connections)).unwrap();
+    assert_eq!(json.find_path(&["size", "height"]), Some(&Json::U64(7)));
+    let block = &json["blocks"][0];
+    assert_eq!(block["name"], Json::String("a".to_owned()));
+    assert_eq!(block["color"], Json::String("red".to_owned()));
+    assert_eq!(block["pos"]["x"], Json::U64(0));
+    let conn = &json["connections"][0];
+    assert_eq!(conn["end_arrow"], Json::Boolean(true));
+    assert_eq!(conn["parts"][0]["end"]["y"], Json::U64(6));
+    assert_eq!(conn["label"]["pos"], Json::Null);
+  }
+}
@@ -0,0 +1,156 @@
+use data::Coloring;
+use layout::charset::{CharSet, Sides};
+use layout::display::{ConnectionDisplay, ConnectionPart, Position};
+use std::collections::HashMap;
+use super::Canvas;
+
+/// A cell that more than one connection passes through.
+#[derive(Debug, PartialEq, Eq)]
+struct Shared {
+  /// Sides each connection leaves the cell by, in drawing order
+  passes: Vec<(Sides, Coloring)>
+}
+
+/// Redraws every cell shared by several connections so that it shows
+/// how they meet, instead of just the connection drawn last.
+///
+/// Two connections crossing straight over each other are unrelated, so
+/// the vertical one hops over the horizontal one. Anywhere else
+/// connections merge, and the cell gets the junction joining all of
+/// their lines. The ends of connections are left alone.
+pub fn draw_junctions<C: Canvas>(
+  canvas: &mut C,
+  offset: Position,
+  connections: &[ConnectionDisplay],
+  charset: CharSet) {
+  for (pos, shared) in shared_cells(connections).into_iter() {
+    let (character, color) = shared.glyph(charset);
+    canvas.set_style(color);
+    canvas.place_char(pos + offset, character);
+  }
+  canvas.set_style(Coloring::Default);
+}
+
+impl Shared {
+  fn glyph(&self, charset: CharSet) -> (char, Coloring) {
+    if self.passes.len() == 2 {
+      let (a, b) = (self.passes[0], self.passes[1]);
+      if a.0.is_vertical() && b.0.is_horizontal() {
+        return (charset.hop(), a.1);
+      }
+      if a.0.is_horizontal() && b.0.is_vertical() {
+        return (charset.hop(), b.1);
+      }
+    }
+
+    let sides = self.passes.iter().fold(Sides::default(), |all, pass| all.union(pass.0));
+    (charset.junction(sides), self.passes[self.passes.len() - 1].1)
+  }
+}
+
+fn shared_cells(connections: &[ConnectionDisplay]) -> Vec<(Position, Shared)> {
+  let mut cells: HashMap<Position, Shared> = HashMap::new();
+  let mut ends: Vec<Position> = vec![];
+
+  for conn in connections.iter() {
+    if let (Some(first), Some(last)) = (conn.parts.first(), conn.parts.last()) {
+      ends.push(first.start);
+      ends.push(last.end);
+    }
+    for (pos, sides) in connection_sides(conn).into_iter() {
+      cells.entry(pos).or_insert(Shared{passes: vec![]}).passes.push((sides, conn.color));
+    }
+  }
+
+  let mut shared:Vec<(Position, Shared)> =
+    cells.into_iter().filter(
+      |&(ref pos, ref shared)| shared.passes.len() > 1 && !ends.contains(pos)
+    ).collect();
+  // Keeps drawing order stable, which matters to canvases like ncurses
+  shared.sort_by(|a, b| (a.0.y, a.0.x).cmp(&(b.0.y, b.0.x)));
+  shared
+}
+
+/// The sides every cell of a connection leaves by. Bends get the sides
+/// of both parts that meet there.
+fn connection_sides(conn: &ConnectionDisplay) -> HashMap<Position, Sides> {
+  let mut sides: HashMap<Position, Sides> = HashMap::new();
+  for part in conn.parts.iter() {
+    for pos in part.positions() {
+      let cell = sides.entry(pos).or_insert(Sides::default());
+      *cell = cell.union(part_sides(part, pos));
+    }
+  }
+  sides
+}
+
+fn part_sides(part: &ConnectionPart, pos: Position) -> Sides {
+  let (low, high) =
+    if part.start.x + part.start.y <= part.end.x + part.end.y {
+      (part.start, part.end)
+    } else {
+      (part.end, part.start)
+    };
+  if low.x == high.x {
+    Sides{up: pos.y > low.y, down: pos.y < high.y, ..Sides::default()}
+  } else {
+    Sides{left: pos.x > low.x, right: pos.x < high.x, ..Sides::default()}
+  }
+}
+
+#[cfg(test)]
+mod test {
+  use data::Coloring;
+  use layout::charset::CharSet;
+  use layout::display::*;
+  use render::grid::CharGrid;
+  use render::connection::draw_connection;
+  use super::draw_junctions;
+
+  fn conn(points: &[(u32, u32)]) -> ConnectionDisplay {
+    ConnectionDisplay{
+      parts: points.windows(2).map(
+        |w| ConnectionPart{
+          start: Position{x: w[0].0, y: w[0].1},
+          end: Position{x: w[1].0, y: w[1].1}}
+      ).collect(),
+      color: Coloring::Default,
+      start_arrow: false,
+      end_arrow: true,
+      label: None}
+  }
+
+  fn render(connections: &[ConnectionDisplay], charset: CharSet) -> Vec<String> {
+    let mut grid = CharGrid::new();
+    for c in connections.iter() {
+      draw_connection(&mut grid, Position{x: 0, y: 0}, c, charset);
+    }
+    draw_junctions(&mut grid, Position{x: 0, y: 0}, connections, charset);
+    grid.lines()
+  }
+
+  #[test]
+  fn unrelated_crossing_leaves_a_gap() {
+    let lines = render(&[conn(&[(2, 0), (2, 4)]), conn(&[(0, 2), (4, 2)])], CharSet::Unicode);
+    assert_eq!(lines[2], "●─│─▶");
+  }
+
+  #[test]
+  fn ascii_crossing_hops_and_merging_joins() {
+    let lines = render(&[conn(&[(2, 0), (2, 4)]), conn(&[(0, 2), (4, 2)])], CharSet::Ascii);
+    assert_eq!(lines[2], "#-)->");
+
+    let lines = render(&[conn(&[(2, 0), (2, 2), (4, 2)]), conn(&[(0, 2), (4, 2)])], CharSet::Ascii);
+    assert_eq!(lines[2], "#-+->");
+  }
+
+  #[test]
+  fn merging_connections_get_junctions() {
+    // The second connection runs into the first and follows it down
+    let lines = render(&[conn(&[(2, 0), (2, 4)]), conn(&[(0, 2), (2, 2), (2, 4)])], CharSet::Unicode);
+    assert_eq!(lines[2], "●─┤");
+
+    let lines = render(&[conn(&[(2, 0), (2, 2), (4, 2)]), conn(&[(0, 2), (4, 2)])], CharSet::Unicode);
+    assert_eq!(lines[2], "●─┴─▶");
+  }
+}
@@ -1,14 +1,78 @@
+pub mod ansi;
 pub mod block;
 pub mod connection;
-use ncurses::{chtype, mvaddch};
-use layout::display::Position;
+pub mod curses;
+pub mod grid;
+pub mod html;
+pub mod json;
+pub mod junction;
+pub mod svg;
+pub mod text;
+pub mod viewer;
 
-pub trait CharPositioner{
-  fn place_char(&self, character: char);
+use data::{BlockSpec, Coloring};
+use layout::display::{BlockDisplay, ConnectionDisplay, Position, Size};
+use std::cmp::max;
+
+/// A drawing surface that blocks and connections can be rendered onto.
+///
+/// Implementations decide what placing a character actually means, so the
+/// drawing code in `block` and `connection` works for any output target.
+pub trait Canvas {
+  fn place_char(&mut self, pos: Position, character: char);
+
+  fn write_str(&mut self, pos: Position, text: &str) {
+    for (i, c) in (0u32..).zip(text.chars()) {
+      self.place_char(pos.add_x(i), c);
+    }
+  }
+
+  /// Sets the colour used for everything drawn until the next call.
+  /// `Coloring::Default` returns to the canvas' normal style.
+  fn set_style(&mut self, color: Coloring);
+}
+
+/// The size of the area a laid out diagram covers, measured from
+/// the origin.
+pub fn diagram_size(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay])
+    -> Size {
+  let mut size = Size{width: 0, height: 0};
+
+  for &(_, ref block) in blocks.iter() {
+    let far = block.pos.add_size(block.size);
+    size.width = max(size.width, far.x);
+    size.height = max(size.height, far.y);
+  }
+  for conn in connections.iter() {
+    for part in conn.parts.iter() {
+      size.width = max(size.width, max(part.start.x, part.end.x) + 1);
+      size.height = max(size.height, max(part.start.y, part.end.y) + 1);
+    }
+    if let Some(ref label) = conn.label {
+      for pos in label.pos {
+        size.width = max(size.width, pos.x + label.text.chars().count() as u32);
+        size.height = max(size.height, pos.y + 1);
+      }
+    }
+  }
+
+  size
 }
 
-impl CharPositioner for Position {
-  fn place_char(&self, character: char) {
-    mvaddch(self.y as i32, self.x as i32, character as chtype);
+/// CSS colour for a coloring. SVG and HTML output is normally shown on a
+/// light background, so white is drawn as grey to stay visible.
+pub fn css_color(color: Coloring) -> &'static str {
+  use data::Coloring::*;
+  match color {
+    Default | Black => "black",
+    White => "silver",
+    Red => "red",
+    Green => "green",
+    Yellow => "gold",
+    Blue => "blue",
+    Magenta => "magenta",
+    Cyan => "darkcyan"
   }
 }
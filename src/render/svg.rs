@@ -0,0 +1,169 @@
+use data::{BlockSpec, Coloring};
+use layout::display::{BlockDisplay, ConnectionDisplay, Position};
+use std::fmt::Write;
+use super::{css_color, diagram_size};
+
+/// Width in pixels of a single grid cell
+const CELL_WIDTH: u32 = 10;
+/// Height in pixels of a single grid cell
+const CELL_HEIGHT: u32 = 20;
+
+const ALL_COLORS: [Coloring; 9] = [
+  Coloring::Default,
+  Coloring::Black,
+  Coloring::White,
+  Coloring::Red,
+  Coloring::Green,
+  Coloring::Yellow,
+  Coloring::Blue,
+  Coloring::Magenta,
+  Coloring::Cyan];
+
+/// Renders a laid out diagram as an SVG document.
+///
+/// Grid positions from the layout are scaled by the cell size, so the SVG
+/// has the same arrangement as the terminal output. Lines are drawn through
+/// the centre of the cells they occupy.
+pub fn render_to_string(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay])
+    -> String {
+  let size = diagram_size(blocks, connections);
+
+  let mut out = String::new();
+  writeln!(out,
+           "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{}\" height=\"{}\" \
+            font-family=\"monospace\" font-size=\"{}\">",
+           size.width * CELL_WIDTH, size.height * CELL_HEIGHT, CELL_HEIGHT * 3 / 4).unwrap();
+
+  out.push_str("<defs>\n");
+  for color in ALL_COLORS.iter() {
+    writeln!(out,
+             "<marker id=\"arrow-{0}\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+              markerWidth=\"6\" markerHeight=\"6\" orient=\"auto-start-reverse\">\
+              <path d=\"M 0 0 L 10 5 L 0 10 z\" fill=\"{1}\"/></marker>",
+             marker_suffix(*color), css_color(*color)).unwrap();
+  }
+  out.push_str("</defs>\n");
+
+  for &(_, ref block) in blocks.iter() {
+    write_block(&mut out, block);
+  }
+  for conn in connections.iter() {
+    write_connection(&mut out, conn);
+  }
+
+  out.push_str("</svg>\n");
+  out
+}
+
+/// Prints a laid out diagram to stdout as an SVG document.
+pub fn print_diagram(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay]) {
+  print!("{}", render_to_string(blocks, connections));
+}
+
+fn write_block(out: &mut String, block: &BlockDisplay) {
+  let color = css_color(block.color);
+  let top_left = cell_center(block.pos);
+  writeln!(out,
+           "<rect x=\"{}\" y=\"{}\" width=\"{}\" height=\"{}\" fill=\"none\" stroke=\"{}\"/>",
+           top_left.0, top_left.1,
+           (block.size.width - 1) * CELL_WIDTH, (block.size.height - 1) * CELL_HEIGHT,
+           color).unwrap();
+
+  for (i, content) in (0u32..).zip(block.content_lines.iter()) {
+    let pos = block.pos.add_y(i + 1).add_x(2);
+    writeln!(out,
+             "<text x=\"{}\" y=\"{}\" dominant-baseline=\"central\" fill=\"{}\">{}</text>",
+             pos.x * CELL_WIDTH, cell_center(pos).1, color, escape(content)).unwrap();
+  }
+}
+
+fn write_connection(out: &mut String, conn: &ConnectionDisplay) {
+  if conn.parts.is_empty() {
+    return;
+  }
+
+  let mut points = vec![cell_center(conn.parts[0].start)];
+  for part in conn.parts.iter() {
+    points.push(cell_center(part.end));
+  }
+  let point_strs:Vec<String> =
+    points.iter().map(|&(x, y)| format!("{},{}", x, y)).collect();
+
+  let marker = format!("url(#arrow-{})", marker_suffix(conn.color));
+  let mut markers = String::new();
+  if conn.start_arrow {
+    write!(markers, " marker-start=\"{}\"", marker).unwrap();
+  }
+  if conn.end_arrow {
+    write!(markers, " marker-end=\"{}\"", marker).unwrap();
+  }
+
+  writeln!(out,
+           "<polyline points=\"{}\" fill=\"none\" stroke=\"{}\"{}/>",
+           point_strs.join(" "), css_color(conn.color), markers).unwrap();
+
+  if let Some(ref label) = conn.label {
+    for pos in label.pos {
+      writeln!(out,
+               "<text x=\"{}\" y=\"{}\" dominant-baseline=\"central\" fill=\"{}\">{}</text>",
+               pos.x * CELL_WIDTH, cell_center(pos).1, css_color(conn.color),
+               escape(&label.text)).unwrap();
+    }
+  }
+}
+
+fn cell_center(pos: Position) -> (u32, u32) {
+  (pos.x * CELL_WIDTH + CELL_WIDTH / 2, pos.y * CELL_HEIGHT + CELL_HEIGHT / 2)
+}
+
+fn marker_suffix(color: Coloring) -> &'static str {
+  use data::Coloring::*;
+  match color {
+    Default => "default",
+    Black => "black",
+    White => "white",
+    Red => "red",
+    Green => "green",
+    Yellow => "yellow",
+    Blue => "blue",
+    Magenta => "magenta",
+    Cyan => "cyan"
+  }
+}
+
+fn escape(text: &str) -> String {
+  text.replace("&", "&amp;").replace("<", "&lt;").replace(">", "&gt;")
+}
+
+#[cfg(test)]
+mod test {
+  use super::render_to_string;
+  use data::Coloring;
+  use layout::display::*;
+
+  #[test]
+  fn arrow_markers_follow_arrowheads() {
+    let conn = ConnectionDisplay{
+      parts: vec![
+        ConnectionPart{
+          start: Position{x:0, y:0},
+          end: Position{x:0, y:2}},
+        ConnectionPart{
+          start: Position{x:0, y:2},
+          end: Position{x:3, y:2}}],
+      color: Coloring::Red,
+      start_arrow: false,
+      end_arrow: true,
+      label: None};
+
+    let svg = render_to_string(&[], &[conn]);
+    assert!(svg.contains(
+      "<polyline points=\"5,10 5,50 35,50\" fill=\"none\" stroke=\"red\" \
+       marker-end=\"url(#arrow-red)\"/>"));
+    assert!(!svg.contains("marker-start=\""));
+  }
+}
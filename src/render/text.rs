@@ -0,0 +1,106 @@
+use data::BlockSpec;
+use layout::charset::CharSet;
+use layout::display::{BlockDisplay, ConnectionDisplay, Position};
+use super::grid::CharGrid;
+use super::block::draw_block_display;
+use super::connection::draw_connection;
+use super::junction::draw_junctions;
+
+/// Rasterizes a laid out diagram into a character grid.
+///
+/// Blocks are drawn first, then connections on top of them, which matches
+/// the order used by the ncurses frontend. Cells that several connections
+/// pass through are then redrawn to show how they meet.
+pub fn rasterize(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet)
+    -> CharGrid {
+  let mut grid = CharGrid::new();
+
+  for &(_, ref block) in blocks.iter() {
+    draw_block_display(&mut grid, Position{x:0, y:0}, block, charset);
+  }
+  for connection in connections.iter() {
+    draw_connection(&mut grid, Position{x:0, y:0}, connection, charset);
+  }
+  draw_junctions(&mut grid, Position{x:0, y:0}, connections, charset);
+
+  grid
+}
+
+/// Renders a laid out diagram to plain text, one line per grid row.
+pub fn render_to_string(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet)
+    -> String {
+  let mut out = String::new();
+  for line in rasterize(blocks, connections, charset).lines() {
+    out.push_str(&line);
+    out.push('\n');
+  }
+  out
+}
+
+/// Prints a laid out diagram to stdout as plain text.
+pub fn print_diagram(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet) {
+  print!("{}", render_to_string(blocks, connections, charset));
+}
+
+#[cfg(test)]
+mod test {
+  use super::render_to_string;
+  use data::{BlockSpec, Coloring};
+  use layout::charset::CharSet;
+  use layout::display::*;
+
+  #[test]
+  fn renders_block_and_connection() {
+    let spec = BlockSpec::Boxed("a".to_owned(), Coloring::Default, "hi".to_owned());
+    let block = BlockDisplay{
+      color: Coloring::Default,
+      content_lines: vec!["hi".to_owned()],
+      pos: Position{x:0, y:0},
+      size: Size{width:6, height:3}};
+    let conn = ConnectionDisplay{
+      parts: vec![ConnectionPart{
+        start: Position{x:3, y:2},
+        end: Position{x:3, y:5}}],
+      color: Coloring::Default,
+      start_arrow: false,
+      end_arrow: true,
+      label: Some(ConnectionLabel{text: "ok".to_owned(), pos: Some(Position{x:5, y:4})})};
+
+    let text = render_to_string(&[(&spec, block)], &[conn], CharSet::Ascii);
+    assert_eq!(text, "+----+\n| hi |\n+--#-+\n   |\n   | ok\n   v\n");
+  }
+
+  #[test]
+  fn renders_unicode_elbows() {
+    let spec = BlockSpec::Boxed("a".to_owned(), Coloring::Default, "hi".to_owned());
+    let block = BlockDisplay{
+      color: Coloring::Default,
+      content_lines: vec!["hi".to_owned()],
+      pos: Position{x:0, y:0},
+      size: Size{width:6, height:3}};
+    let conn = ConnectionDisplay{
+      parts: vec![
+        ConnectionPart{
+          start: Position{x:3, y:2},
+          end: Position{x:3, y:4}},
+        ConnectionPart{
+          start: Position{x:3, y:4},
+          end: Position{x:7, y:4}}],
+      color: Coloring::Default,
+      start_arrow: false,
+      end_arrow: true,
+      label: None};
+
+    let text = render_to_string(&[(&spec, block)], &[conn], CharSet::Rounded);
+    assert_eq!(text, "╭────╮\n│ hi │\n╰──●─╯\n   │\n   ╰───▶\n");
+  }
+}
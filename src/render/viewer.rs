@@ -0,0 +1,251 @@
+use data::{BlockSpec, Coloring};
+use layout::charset::CharSet;
+use layout::display::{BlockDisplay, ConnectionDisplay, Position, Size};
+use ncurses::*;
+use std::cmp::min;
+use super::block::draw_block_display;
+use super::connection::draw_connection;
+use super::junction::draw_junctions;
+use super::curses::NcursesCanvas;
+use super::{Canvas, diagram_size};
+
+const KEY_ESCAPE: i32 = 27;
+/// How often `view_until_changed` checks for changes, in milliseconds
+const POLL_INTERVAL: i32 = 250;
+
+/// The part of a diagram that is visible on the screen.
+///
+/// Diagrams smaller than the screen are centred on it, larger ones can be
+/// scrolled until their far edge reaches the edge of the screen.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub struct Viewport {
+  /// Position in the diagram shown at the top left of the screen
+  pub scroll: Position,
+  pub screen: Size,
+  pub diagram: Size
+}
+
+impl Viewport {
+  pub fn new(diagram: Size, screen: Size) -> Viewport {
+    Viewport{scroll: Position{x: 0, y: 0}, screen: screen, diagram: diagram}
+  }
+
+  pub fn resize(&mut self, screen: Size) {
+    self.screen = screen;
+    self.scroll_by(0, 0);
+  }
+
+  /// Moves the viewport, keeping it within the diagram.
+  pub fn scroll_by(&mut self, dx: i32, dy: i32) {
+    self.scroll.x = clamped_add(self.scroll.x, dx, self.diagram.width.saturating_sub(self.screen.width));
+    self.scroll.y = clamped_add(self.scroll.y, dy, self.diagram.height.saturating_sub(self.screen.height));
+  }
+
+  /// Offset to draw the diagram at so that it is centred when it is
+  /// smaller than the screen.
+  pub fn offset(&self) -> Position {
+    Position{
+      x: self.screen.width.saturating_sub(self.diagram.width) / 2,
+      y: self.screen.height.saturating_sub(self.diagram.height) / 2}
+  }
+}
+
+fn clamped_add(value: u32, change: i32, upper: u32) -> u32 {
+  let moved = value as i64 + change as i64;
+  if moved < 0 {
+    0
+  } else {
+    min(moved as u32, upper)
+  }
+}
+
+/// Canvas that shifts everything drawn on it by the viewport's scroll
+/// position, and drops anything that ends up off the screen.
+struct ScrolledCanvas<'a, C: 'a + Canvas> {
+  inner: &'a mut C,
+  viewport: Viewport
+}
+
+impl<'a, C: Canvas> Canvas for ScrolledCanvas<'a, C> {
+  fn place_char(&mut self, pos: Position, character: char) {
+    let scroll = self.viewport.scroll;
+    if pos.x < scroll.x || pos.y < scroll.y {
+      return;
+    }
+    let shifted = Position{x: pos.x - scroll.x, y: pos.y - scroll.y};
+    if shifted.x < self.viewport.screen.width && shifted.y < self.viewport.screen.height {
+      self.inner.place_char(shifted, character);
+    }
+  }
+
+  fn set_style(&mut self, color: Coloring) {
+    self.inner.set_style(color);
+  }
+}
+
+fn screen_size() -> Size {
+  let (mut height, mut width) = (0, 0);
+  getmaxyx(stdscr(), &mut height, &mut width);
+  Size{width: width as u32, height: height as u32}
+}
+
+/// Shows a diagram on the ncurses screen until the user quits with `q`.
+///
+/// The view can be moved with the arrow keys or `hjkl`, and a screen
+/// at a time with page up and page down. It is redrawn whenever the
+/// terminal is resized. ncurses must already be initialized.
+pub fn run_viewer(
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet) {
+  start_viewer();
+
+  let mut viewport = Viewport::new(diagram_size(blocks, connections), screen_size());
+
+  loop {
+    viewport.resize(screen_size());
+    draw_view(&viewport, blocks, connections, charset, None);
+    if !handle_key(getch(), &mut viewport) {
+      break;
+    }
+  }
+}
+
+/// Shows a diagram like `run_viewer`, but also calls `changed` every
+/// so often, returning true as soon as it does. Returns false if the user
+/// quit instead.
+///
+/// The viewport is kept between calls, so that a diagram that is shown
+/// again after changing stays scrolled to the same place. A status line
+/// is drawn along the bottom of the screen when there is one.
+pub fn view_until_changed<F>(
+  viewport: &mut Viewport,
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet,
+  status: Option<&str>,
+  mut changed: F)
+    -> bool where F: FnMut() -> bool {
+  start_viewer();
+  timeout(POLL_INTERVAL);
+  viewport.diagram = diagram_size(blocks, connections);
+
+  let mut redraw = true;
+  loop {
+    if redraw {
+      let mut screen = screen_size();
+      if status.is_some() {
+        screen.height = screen.height.saturating_sub(1);
+      }
+      viewport.resize(screen);
+      draw_view(viewport, blocks, connections, charset, status);
+    }
+
+    let key = getch();
+    if key == ERR {
+      if changed() {
+        timeout(-1);
+        return true;
+      }
+      redraw = false;
+    } else if !handle_key(key, viewport) {
+      timeout(-1);
+      return false;
+    } else {
+      redraw = true;
+    }
+  }
+}
+
+fn start_viewer() {
+  keypad(stdscr(), true);
+  noecho();
+  curs_set(CURSOR_VISIBILITY::CURSOR_INVISIBLE);
+}
+
+/// Acts on a key press. Returns false if the key quits the viewer, or if
+/// input has ended.
+fn handle_key(key: i32, viewport: &mut Viewport) -> bool {
+  let page_height = viewport.screen.height as i32;
+  let page_width = viewport.screen.width as i32;
+  match key {
+    KEY_UP => viewport.scroll_by(0, -1),
+    KEY_DOWN => viewport.scroll_by(0, 1),
+    KEY_LEFT => viewport.scroll_by(-1, 0),
+    KEY_RIGHT => viewport.scroll_by(1, 0),
+    KEY_PPAGE => viewport.scroll_by(0, -page_height),
+    KEY_NPAGE => viewport.scroll_by(0, page_height),
+    KEY_HOME => viewport.scroll_by(-page_width, 0),
+    KEY_END => viewport.scroll_by(page_width, 0),
+    KEY_ESCAPE => return false,
+    // A blocking getch only fails once there is no more input to read
+    ERR => return false,
+    key if key == 'k' as i32 => viewport.scroll_by(0, -1),
+    key if key == 'j' as i32 => viewport.scroll_by(0, 1),
+    key if key == 'h' as i32 => viewport.scroll_by(-1, 0),
+    key if key == 'l' as i32 => viewport.scroll_by(1, 0),
+    key if key == 'q' as i32 => return false,
+    // Includes KEY_RESIZE, which just needs the viewer to redraw
+    _ => ()
+  }
+  true
+}
+
+fn draw_view(
+  viewport: &Viewport,
+  blocks: &[(&BlockSpec, BlockDisplay)],
+  connections: &[ConnectionDisplay],
+  charset: CharSet,
+  status: Option<&str>) {
+  erase();
+
+  let mut screen = NcursesCanvas::new();
+  let mut canvas = ScrolledCanvas{inner: &mut screen, viewport: *viewport};
+  for &(_, ref block) in blocks.iter() {
+    draw_block_display(&mut canvas, viewport.offset(), block, charset);
+  }
+  for connection in connections.iter() {
+    draw_connection(&mut canvas, viewport.offset(), connection, charset);
+  }
+  draw_junctions(&mut canvas, viewport.offset(), connections, charset);
+
+  for text in status {
+    draw_status(text);
+  }
+
+  refresh();
+}
+
+/// Draws the first line of `text` in reverse video across the bottom
+/// line of the screen.
+fn draw_status(text: &str) {
+  let screen = screen_size();
+  let line:String = text.lines().next().unwrap_or("").chars().take(screen.width as usize).collect();
+  attron(A_REVERSE());
+  mvprintw(screen.height as i32 - 1, 0, &format!("{:1$}", line, screen.width as usize));
+  attroff(A_REVERSE());
+}
+
+#[cfg(test)]
+mod test {
+  use super::Viewport;
+  use layout::display::{Position, Size};
+
+  #[test]
+  fn scrolling_stays_in_diagram() {
+    let mut viewport = Viewport::new(Size{width: 100, height: 60}, Size{width: 80, height: 24});
+    viewport.scroll_by(-5, 10);
+    assert_eq!(viewport.scroll, Position{x: 0, y: 10});
+    viewport.scroll_by(50, 50);
+    assert_eq!(viewport.scroll, Position{x: 20, y: 36});
+
+    viewport.resize(Size{width: 120, height: 40});
+    assert_eq!(viewport.scroll, Position{x: 0, y: 20});
+  }
+
+  #[test]
+  fn small_diagrams_are_centred() {
+    let viewport = Viewport::new(Size{width: 40, height: 10}, Size{width: 80, height: 24});
+    assert_eq!(viewport.offset(), Position{x: 20, y: 7});
+  }
+}
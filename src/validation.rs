@@ -0,0 +1,166 @@
+use data::{DataSpec, BlockSpec, Connection};
+use parser::driver::LocatedSpec;
+use parser::location::SourceLocation;
+use std::collections::HashMap;
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum DiagnosticKind {
+  /// A connection names a block that is never defined
+  UnknownEndpoint(String),
+  /// A block reuses a name. Holds the location of the first definition.
+  DuplicateName(String, SourceLocation),
+  /// A connection starts and ends at the same block
+  SelfLoop(String)
+}
+
+/// A problem found in a parsed specification, and where it is.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+  pub kind: DiagnosticKind,
+  pub location: SourceLocation
+}
+
+impl Display for Diagnostic {
+  fn fmt(&self, f:&mut Formatter) -> Result<(), fmt::Error> {
+    use self::DiagnosticKind::*;
+    try!(write!(f, "{}: ", self.location));
+    try!(match self.kind {
+      UnknownEndpoint(ref name) =>
+        write!(f, "connection refers to unknown block {}", name),
+      DuplicateName(ref name, ref first) =>
+        write!(f, "block {} is already defined at {}", name, first),
+      SelfLoop(ref name) =>
+        write!(f, "connection from block {} to itself", name)
+    });
+    write!(f, "\n{}", self.location.snippet())
+  }
+}
+
+/// Checks a specification for problems that the parser can't see:
+/// connections to blocks that don't exist, blocks that share a name
+/// and connections from a block to itself.
+pub fn validate(specs: &[LocatedSpec]) -> Vec<Diagnostic> {
+  let mut diagnostics = vec![];
+  let mut defined: HashMap<&str, &SourceLocation> = HashMap::new();
+
+  for &(ref location, ref spec) in specs.iter() {
+    if let &DataSpec::BlockDataSpec(ref block) = spec {
+      let name = block.get_name();
+      if let Some(first) = defined.get(name) {
+        diagnostics.push(Diagnostic{
+          kind: DiagnosticKind::DuplicateName(name.to_owned(), (*first).clone()),
+          location: location.clone()});
+        continue;
+      }
+      defined.insert(name, location);
+    }
+  }
+
+  for &(ref location, ref spec) in specs.iter() {
+    if let &DataSpec::ConnectionDataSpec(ref conn) = spec {
+      for name in unknown_endpoints(conn, &defined) {
+        diagnostics.push(Diagnostic{
+          kind: DiagnosticKind::UnknownEndpoint(name.to_owned()),
+          location: location.clone()});
+      }
+      if conn.start == conn.end {
+        diagnostics.push(Diagnostic{
+          kind: DiagnosticKind::SelfLoop(conn.start.clone()),
+          location: location.clone()});
+      }
+    }
+  }
+
+  diagnostics.sort_by(|a, b| (a.location.line, a.location.column).cmp(&(b.location.line, b.location.column)));
+  diagnostics
+}
+
+/// Removes the parts of a specification that layout can't handle:
+/// every block after the first with a given name, connections to
+/// blocks that don't exist and connections from a block to itself.
+pub fn retain_valid(specs: Vec<LocatedSpec>) -> Vec<DataSpec> {
+  let mut blocks: Vec<BlockSpec> = vec![];
+  let mut connections: Vec<Connection> = vec![];
+
+  for (_, spec) in specs.into_iter() {
+    match spec {
+      DataSpec::BlockDataSpec(block) => {
+        if !blocks.iter().any(|b| b.get_name() == block.get_name()) {
+          blocks.push(block);
+        }
+      }
+      DataSpec::ConnectionDataSpec(conn) => connections.push(conn)
+    }
+  }
+
+  connections.retain(
+    |c| c.start != c.end &&
+        blocks.iter().any(|b| b.get_name() == c.start) &&
+        blocks.iter().any(|b| b.get_name() == c.end));
+
+  blocks.into_iter().map(DataSpec::BlockDataSpec)
+    .chain(connections.into_iter().map(DataSpec::ConnectionDataSpec))
+    .collect()
+}
+
+fn unknown_endpoints<'a>(conn: &'a Connection, defined: &HashMap<&str, &SourceLocation>) -> Vec<&'a str> {
+  let mut unknown = vec![];
+  if !defined.contains_key(&conn.start[..]) {
+    unknown.push(&conn.start[..]);
+  }
+  if conn.end != conn.start && !defined.contains_key(&conn.end[..]) {
+    unknown.push(&conn.end[..]);
+  }
+  unknown
+}
+
+#[cfg(test)]
+mod test {
+  use super::*;
+  use data::{Coloring, ConnectionType};
+  use data::fixtures::conn;
+  use parser::driver::parse_located_specification;
+
+  fn kinds(source: &[u8]) -> Vec<(usize, DiagnosticKind)> {
+    let specs = parse_located_specification(source).unwrap();
+    validate(&specs).into_iter().map(|d| (d.location.line, d.kind)).collect()
+  }
+
+  #[test]
+  fn valid_spec_has_no_diagnostics() {
+    assert_eq!(kinds(b"box text a\n  A\n\nbox text b\n  B\n\nconnection a b\n"), vec![]);
+  }
+
+  #[test]
+  fn reports_unknown_endpoints() {
+    assert_eq!(kinds(b"box text a\n  A\n\nconnection a b\nconnection c a\n"),
+               vec![(4, DiagnosticKind::UnknownEndpoint("b".to_owned())),
+                    (5, DiagnosticKind::UnknownEndpoint("c".to_owned()))]);
+  }
+
+  #[test]
+  fn reports_duplicates_and_self_loops() {
+    let found = kinds(b"box text a\n  A\n\nbox text a\n  again\n\nconnection a a\n");
+    assert_eq!(found.len(), 2);
+    match found[0] {
+      (4, DiagnosticKind::DuplicateName(ref name, ref first)) => {
+        assert_eq!(name, "a");
+        assert_eq!(first.line, 1);
+      }
+      ref other => panic!("Expected a duplicate name, got {:?}", other)
+    }
+    assert_eq!(found[1], (7, DiagnosticKind::SelfLoop("a".to_owned())));
+  }
+
+  #[test]
+  fn retain_valid_drops_unusable_specs() {
+    let specs = parse_located_specification(
+      b"box text a\n  A\n\nbox text a\n  again\n\nbox text b\n  B\n\n\
+        connection a b\nconnection a c\nconnection a a\n").unwrap();
+    let kept = retain_valid(specs);
+    assert_eq!(kept.len(), 3);
+    assert_eq!(kept[2], conn(ConnectionType::Generic, "a", "b", Coloring::Default, None));
+  }
+}
@@ -0,0 +1,163 @@
+use data::*;
+
+/// Writes specifications back out as `.cog` source.
+///
+/// Blocks are separated from everything around them by a blank line and
+/// connections are written one per line. Default colours are left out.
+pub fn to_source(specs: &[DataSpec]) -> String {
+  let mut out = String::new();
+  let mut last_was_block = false;
+
+  for (i, spec) in specs.iter().enumerate() {
+    let is_block = if let &DataSpec::BlockDataSpec(_) = spec {true} else {false};
+    if i > 0 && (is_block || last_was_block) {
+      out.push('\n');
+    }
+    match *spec {
+      DataSpec::BlockDataSpec(ref block) => write_block(&mut out, block),
+      DataSpec::ConnectionDataSpec(ref conn) => write_connection(&mut out, conn)
+    }
+    last_was_block = is_block;
+  }
+
+  out
+}
+
+/// Writes a block and its text, ending with a line break.
+pub fn write_block(out: &mut String, block: &BlockSpec) {
+  let BlockSpec::Boxed(_, _, ref text) = *block;
+  out.push_str(&block_header(block));
+  out.push('\n');
+  write_box_text(out, text);
+}
+
+/// The `box text` line of a block, without a line break.
+pub fn block_header(block: &BlockSpec) -> String {
+  let BlockSpec::Boxed(ref name, color, _) = *block;
+  let mut header = format!("box text {}", name);
+  write_color(&mut header, color);
+  header
+}
+
+/// Writes the text of a block, indented below its header.
+///
+/// The text is written as a paragraph when that reads back the same,
+/// and as a `"""` heredoc otherwise.
+pub fn write_box_text(out: &mut String, text: &str) {
+  if needs_heredoc(text) {
+    out.push_str("  \"\"\"\n");
+    for line in text.split('\n') {
+      if !line.is_empty() {
+        out.push_str("  ");
+        out.push_str(line);
+      }
+      out.push('\n');
+    }
+    out.push_str("  \"\"\"\n");
+  } else {
+    for line in text.split('\n') {
+      out.push_str("  ");
+      out.push_str(line);
+      out.push('\n');
+    }
+  }
+}
+
+/// Writes a connection on a single line, ending with a line break.
+pub fn write_connection(out: &mut String, conn: &Connection) {
+  let fields:Vec<String> =
+    connection_fields(conn).into_iter().filter(|f| !f.is_empty()).collect();
+  out.push_str(&fields.join(" "));
+  out.push('\n');
+}
+
+/// The words of a connection line, grouped so that lines can be lined up
+/// with each other: the type, `connection`, both ends, the colour and the
+/// label. The colour and label are empty when the connection has none.
+pub fn connection_fields(conn: &Connection) -> Vec<String> {
+  let mut color = String::new();
+  write_color(&mut color, conn.color);
+  vec![
+    connection_type_name(conn.ty).to_owned(),
+    "connection".to_owned(),
+    conn.start.clone(),
+    conn.end.clone(),
+    color.trim_left().to_owned(),
+    conn.label.as_ref().map(|l| format!("label \"{}\"", l)).unwrap_or(String::new())]
+}
+
+pub fn color_name(color: Coloring) -> &'static str {
+  use data::Coloring::*;
+  match color {
+    Default => "default",
+    Black => "black",
+    White => "white",
+    Red => "red",
+    Green => "green",
+    Yellow => "yellow",
+    Blue => "blue",
+    Magenta => "magenta",
+    Cyan => "cyan"
+  }
+}
+
+pub fn connection_type_name(ty: ConnectionType) -> &'static str {
+  match ty {
+    ConnectionType::Singular => "singular",
+    ConnectionType::Dual => "dual",
+    ConnectionType::Generic => "generic"
+  }
+}
+
+fn write_color(out: &mut String, color: Coloring) {
+  if color != Coloring::Default {
+    out.push_str(" color ");
+    out.push_str(color_name(color));
+  }
+}
+
+/// Whether text can't be written as a paragraph. Paragraph lines lose
+/// their surrounding whitespace and can't be blank, and a first line that
+/// looks like a comment or a heredoc would be read as one.
+fn needs_heredoc(text: &str) -> bool {
+  let first = text.split('\n').next().unwrap_or("");
+  text.split('\n').any(|line| line.is_empty() || line.trim() != line) ||
+    ["#", "//", "/*", "\"\"\""].iter().any(|start| first.starts_with(start))
+}
+
+#[cfg(test)]
+mod test {
+  use super::to_source;
+  use data::*;
+  use data::fixtures::*;
+  use parser::driver::parse_specification;
+
+  #[test]
+  fn writes_readable_source() {
+    let specs = vec![
+      block("a", Coloring::Red, "First"),
+      block("b", Coloring::Default, "Second\nline"),
+      DataSpec::ConnectionDataSpec(Connection{
+        ty: ConnectionType::Singular,
+        start: "a".to_owned(),
+        end: "b".to_owned(),
+        color: Coloring::Default,
+        label: Some("uplink".to_owned())})];
+    assert_eq!(
+      to_source(&specs),
+      "box text a color red\n  First\n\nbox text b\n  Second\n  line\n\n\
+       singular connection a b label \"uplink\"\n");
+    assert_eq!(parse_specification(to_source(&specs).as_bytes()).unwrap(), specs);
+  }
+
+  #[test]
+  fn awkward_text_uses_heredoc() {
+    let specs = vec![
+      block("a", Coloring::Default, "# not a comment\n\n  indented"),
+      block("b", Coloring::Blue, "plain"),
+      block("c", Coloring::Default, "Links\nconnection a b")];
+    let source = to_source(&specs);
+    assert!(source.contains("\"\"\""));
+    assert_eq!(parse_specification(source.as_bytes()).unwrap(), specs);
+  }
+}